@@ -0,0 +1,103 @@
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    web, Error, HttpMessage,
+};
+use std::sync::Arc;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::downstream::{DownstreamCalls, DOWNSTREAM_CALLS};
+use crate::errors::McpError;
+use crate::state::RuntimeState;
+
+// Structured access log: one line per request with request id, user,
+// body sizes, total and downstream latency, and the machine error code.
+// Routine successes are sampled; failed and slow requests are always logged
+// as warnings.
+// Also assigns/propagates X-Request-Id and feeds the request counters
+// shown by /mcp/status.
+
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Load balancer pings are neither logged nor counted.
+const UNLOGGED_PATHS: [&str; 1] = ["/mcp/ping"];
+
+pub async fn access_log(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if UNLOGGED_PATHS.contains(&req.path()) {
+        return next.call(req).await;
+    }
+    let start = Instant::now();
+    let runtime_state = req.app_data::<web::Data<Arc<RuntimeState>>>().cloned();
+    let _in_flight = runtime_state.as_ref().map(|state| state.track_request());
+
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let user_id = crate::middleware::extract_user_id(req.request()).unwrap_or_else(|| "-".to_string());
+    let request_bytes = req
+        .headers()
+        .get("Content-Length")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("-")
+        .to_string();
+
+    let calls = DownstreamCalls::default();
+    req.extensions_mut().insert(calls.clone());
+    let mut res = DOWNSTREAM_CALLS.scope(calls.clone(), next.call(req)).await?;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+
+    let response_bytes = match res.response().body().size() {
+        BodySize::Sized(size) => size.to_string(),
+        BodySize::None => "0".to_string(),
+        BodySize::Stream => "stream".to_string(),
+    };
+    let error_code = res
+        .response()
+        .error()
+        .and_then(|err| err.as_error::<McpError>())
+        .map(McpError::code)
+        .unwrap_or("-");
+
+    let elapsed = start.elapsed();
+    let status = res.status().as_u16();
+    if let Some(level) = crate::logging::outcome_level(status, elapsed) {
+        log::log!(
+            target: "mcp_server::access",
+            level,
+            "request_id={} method={} path={} status={} user_id={} req_bytes={} resp_bytes={} latency_ms={} downstream={} error_code={}",
+            request_id,
+            method,
+            path,
+            status,
+            user_id,
+            request_bytes,
+            response_bytes,
+            elapsed.as_millis(),
+            calls.latency_summary(),
+            error_code,
+        );
+    }
+
+    Ok(res)
+}
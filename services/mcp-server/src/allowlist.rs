@@ -0,0 +1,86 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use reqwest::Url;
+
+// Allowlist of downstream targets, guarding against SSRF through a
+// misconfigured or dynamic base URL. Entries are `host`, `host:port` or a
+// CIDR range matched against literal IP hosts. An empty list allows all.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AllowedTarget {
+    Host { host: String, port: Option<u16> },
+    Network(IpNet),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HostAllowlist {
+    targets: Vec<AllowedTarget>,
+}
+
+impl HostAllowlist {
+    pub fn parse(entries: &[String]) -> Self {
+        let targets = entries
+            .iter()
+            .map(|entry| {
+                if let Ok(net) = entry.parse::<IpNet>() {
+                    return AllowedTarget::Network(net);
+                }
+                if let Ok(ip) = entry.parse::<IpAddr>() {
+                    return AllowedTarget::Network(IpNet::from(ip));
+                }
+                match entry.rsplit_once(':') {
+                    Some((host, port)) => AllowedTarget::Host {
+                        host: host.to_ascii_lowercase(),
+                        port: Some(port.parse().unwrap_or_else(|_| {
+                            panic!(
+                                "DOWNSTREAM_ALLOWED_HOSTS entry '{}' has an invalid port",
+                                entry
+                            )
+                        })),
+                    },
+                    None => AllowedTarget::Host {
+                        host: entry.to_ascii_lowercase(),
+                        port: None,
+                    },
+                }
+            })
+            .collect();
+        Self { targets }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.targets.is_empty()
+    }
+
+    pub fn is_allowed(&self, url: &str) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(_) => return false,
+        };
+        let host = match url.host_str() {
+            Some(host) => host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_ascii_lowercase(),
+            None => return false,
+        };
+        let port = url.port_or_known_default();
+        let ip = host.parse::<IpAddr>().ok();
+
+        self.targets.iter().any(|target| match target {
+            AllowedTarget::Host {
+                host: allowed,
+                port: None,
+            } => *allowed == host,
+            AllowedTarget::Host {
+                host: allowed,
+                port: Some(allowed_port),
+            } => *allowed == host && Some(*allowed_port) == port,
+            AllowedTarget::Network(net) => ip.is_some_and(|ip| net.contains(&ip)),
+        })
+    }
+}
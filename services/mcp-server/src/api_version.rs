@@ -0,0 +1,46 @@
+use actix_web::HttpRequest;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::errors::McpError;
+use crate::models::{ContextFetchRequest, ContextFetchRequestV1};
+
+// Request schema versioning. Clients pick a schema with `X-Api-Version`;
+// older shapes are converted to the current internal model so handlers only
+// ever see the latest one. Requests without the header use the latest.
+
+pub const API_VERSION_HEADER: &str = "X-Api-Version";
+pub const CURRENT_API_VERSION: u32 = 2;
+
+/// Schema version requested by the client.
+pub fn requested_version(req: &HttpRequest) -> Result<u32, McpError> {
+    let value = match req.headers().get(API_VERSION_HEADER) {
+        Some(value) => value,
+        None => return Ok(CURRENT_API_VERSION),
+    };
+
+    let version = value
+        .to_str()
+        .ok()
+        .map(|v| v.trim().trim_start_matches(['v', 'V']))
+        .and_then(|v| v.parse::<u32>().ok());
+    match version {
+        Some(version) if (1..=CURRENT_API_VERSION).contains(&version) => Ok(version),
+        _ => Err(McpError::InvalidRequest(format!(
+            "Unsupported {}: expected 1 to {}",
+            API_VERSION_HEADER, CURRENT_API_VERSION
+        ))),
+    }
+}
+
+/// Parse a context fetch body in the schema for `version`.
+pub fn context_fetch_request(version: u32, body: Value) -> Result<ContextFetchRequest, McpError> {
+    match version {
+        1 => parse::<ContextFetchRequestV1>(body).map(ContextFetchRequest::from),
+        _ => parse::<ContextFetchRequest>(body),
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: Value) -> Result<T, McpError> {
+    serde_json::from_value(body).map_err(McpError::from)
+}
@@ -0,0 +1,27 @@
+use actix_web::http::StatusCode;
+
+// Aggregate status for batch endpoints: 200 when every item succeeded, 207
+// Multi-Status when results are mixed, and an error status when every item
+// failed, so a batch that did nothing never looks like a success.
+
+pub fn aggregate_status(statuses: &[StatusCode]) -> StatusCode {
+    let failures: Vec<StatusCode> = statuses
+        .iter()
+        .copied()
+        .filter(|status| !status.is_success())
+        .collect();
+
+    if failures.is_empty() {
+        return StatusCode::OK;
+    }
+    if failures.len() < statuses.len() {
+        return StatusCode::MULTI_STATUS;
+    }
+    // All failed: pass a shared failure status through, otherwise report a
+    // generic downstream failure
+    if failures.iter().all(|status| *status == failures[0]) {
+        failures[0]
+    } else {
+        StatusCode::BAD_GATEWAY
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::errors::McpError;
+
+// Consecutive-failure circuit breaker for downstream clients.
+// Opens after `failure_threshold` failures in a row, rejects calls until
+// `reset_timeout` has elapsed, then lets trial calls through (half-open).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+pub struct CircuitBreaker {
+    name: String,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    consecutive_failures: AtomicU32,
+    opened_at: Mutex<Option<Instant>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &str, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            failure_threshold: failure_threshold.max(1),
+            reset_timeout,
+            consecutive_failures: AtomicU32::new(0),
+            opened_at: Mutex::new(None),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let opened_at = self.opened_at.lock().unwrap_or_else(|e| e.into_inner());
+        match *opened_at {
+            None => CircuitState::Closed,
+            Some(at) if at.elapsed() >= self.reset_timeout => CircuitState::HalfOpen,
+            Some(_) => CircuitState::Open,
+        }
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        let mut opened_at = self.opened_at.lock().unwrap_or_else(|e| e.into_inner());
+        if opened_at.take().is_some() {
+            log::info!("{} circuit closed", self.name);
+        }
+    }
+
+    pub fn record_failure(&self) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= self.failure_threshold {
+            let mut opened_at = self.opened_at.lock().unwrap_or_else(|e| e.into_inner());
+            if opened_at.is_none() {
+                log::warn!("{} circuit opened after {} consecutive failures", self.name, failures);
+            }
+            *opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Run a downstream call through the breaker. Only outages (connect
+    /// failures, timeouts and 5xx, all surfaced as `ServiceUnavailable` or
+    /// `GatewayTimeout`) count towards tripping. Client-side outcomes such as
+    /// a user over quota or an unknown id say nothing about the downstream's
+    /// health and leave the breaker untouched.
+    pub async fn call<T, F>(&self, fut: F) -> Result<T, McpError>
+    where
+        F: Future<Output = Result<T, McpError>>,
+    {
+        if self.state() == CircuitState::Open {
+            return Err(McpError::ServiceUnavailable(format!(
+                "{} circuit is open",
+                self.name
+            )));
+        }
+
+        let result = fut.await;
+        match &result {
+            Ok(_) => self.record_success(),
+            Err(McpError::ServiceUnavailable(_)) | Err(McpError::GatewayTimeout(_)) => {
+                self.record_failure()
+            }
+            Err(_) => {}
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn fail_with(breaker: &CircuitBreaker, err: fn() -> McpError, times: usize) {
+        for _ in 0..times {
+            let _ = breaker.call(async { Err::<(), _>(err()) }).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn outages_open_the_circuit() {
+        let breaker = CircuitBreaker::new("test", 3, Duration::from_secs(30));
+        fail_with(&breaker, || McpError::ServiceUnavailable("down".to_string()), 3).await;
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn client_errors_do_not_open_the_circuit() {
+        let breaker = CircuitBreaker::new("test", 3, Duration::from_secs(30));
+        let over_quota = || McpError::TooManyRequests {
+            message: "quota".to_string(),
+            retry_after_secs: 60,
+        };
+        fail_with(&breaker, over_quota, 5).await;
+        fail_with(&breaker, || McpError::NotFound("session".to_string()), 5).await;
+        fail_with(&breaker, || McpError::InvalidRequest("empty".to_string()), 5).await;
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn client_errors_do_not_reset_the_failure_count() {
+        let breaker = CircuitBreaker::new("test", 2, Duration::from_secs(30));
+        fail_with(&breaker, || McpError::GatewayTimeout("slow".to_string()), 1).await;
+        fail_with(&breaker, || McpError::NotFound("session".to_string()), 1).await;
+        fail_with(&breaker, || McpError::GatewayTimeout("slow".to_string()), 1).await;
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+}
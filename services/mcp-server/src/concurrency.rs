@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error, HttpRequest,
+};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard, OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+use crate::errors::McpError;
+use crate::metrics;
+
+// Per-session serialization for task submission.
+// Calls sharing a session_id run one at a time; different sessions stay
+// concurrent. Entries are removed once no caller holds or waits on them.
+
+pub struct SessionLocks {
+    locks: Mutex<HashMap<Uuid, Arc<AsyncMutex<()>>>>,
+    acquire_timeout: Duration,
+}
+
+pub struct SessionGuard<'a> {
+    owner: &'a SessionLocks,
+    session_id: Uuid,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl SessionLocks {
+    pub fn new(acquire_timeout: Duration) -> Self {
+        Self {
+            locks: Mutex::new(HashMap::new()),
+            acquire_timeout,
+        }
+    }
+
+    pub async fn acquire(&self, session_id: Uuid) -> Result<SessionGuard<'_>, McpError> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+            locks.entry(session_id).or_default().clone()
+        };
+
+        match tokio::time::timeout(self.acquire_timeout, lock.lock_owned()).await {
+            Ok(guard) => Ok(SessionGuard {
+                owner: self,
+                session_id,
+                guard: Some(guard),
+            }),
+            Err(_) => Err(McpError::ServiceUnavailable(format!(
+                "Session {} is busy with another request",
+                session_id
+            ))),
+        }
+    }
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        // Release the session first so the map only holds its own reference
+        // when nobody else is waiting.
+        self.guard.take();
+        let mut locks = self.owner.locks.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(lock) = locks.get(&self.session_id) {
+            if Arc::strong_count(lock) == 1 {
+                locks.remove(&self.session_id);
+            }
+        }
+    }
+}
+
+// Per-user cap on in-flight task submissions, so one user can't starve the
+// others. Over-limit calls are rejected immediately rather than queued.
+// A cap of zero disables the limit.
+
+/// Suggested client back-off when a user is at their in-flight limit.
+const USER_TASK_RETRY_AFTER_SECS: u64 = 1;
+
+pub struct UserTaskLimits {
+    permits: Mutex<HashMap<String, Arc<Semaphore>>>,
+    max_per_user: usize,
+}
+
+pub struct UserTaskGuard<'a> {
+    owner: &'a UserTaskLimits,
+    user_id: String,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl UserTaskLimits {
+    /// `max_per_user` of zero disables the limit.
+    pub fn new(max_per_user: usize) -> Self {
+        Self {
+            permits: Mutex::new(HashMap::new()),
+            max_per_user,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_per_user > 0
+    }
+
+    pub fn try_acquire(&self, user_id: &str) -> Result<UserTaskGuard<'_>, McpError> {
+        if !self.is_enabled() {
+            metrics::observe_user_task_started(user_id);
+            return Ok(UserTaskGuard {
+                owner: self,
+                user_id: user_id.to_string(),
+                permit: None,
+            });
+        }
+
+        let semaphore = {
+            let mut permits = self.permits.lock().unwrap_or_else(|e| e.into_inner());
+            permits
+                .entry(user_id.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_user)))
+                .clone()
+        };
+
+        match semaphore.try_acquire_owned() {
+            Ok(permit) => {
+                metrics::observe_user_task_started(user_id);
+                Ok(UserTaskGuard {
+                    owner: self,
+                    user_id: user_id.to_string(),
+                    permit: Some(permit),
+                })
+            }
+            Err(_) => Err(McpError::TooManyRequests {
+                message: format!(
+                    "User already has {} tasks in flight",
+                    self.max_per_user
+                ),
+                retry_after_secs: USER_TASK_RETRY_AFTER_SECS,
+            }),
+        }
+    }
+}
+
+impl Drop for UserTaskGuard<'_> {
+    fn drop(&mut self) {
+        let limited = self.permit.take().is_some();
+        metrics::observe_user_task_finished(&self.user_id);
+        if !limited {
+            return;
+        }
+        // Drop idle users so the map only holds users with work in flight
+        let mut permits = self.owner.permits.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(semaphore) = permits.get(&self.user_id) {
+            if Arc::strong_count(semaphore) == 1
+                && semaphore.available_permits() == self.owner.max_per_user
+            {
+                permits.remove(&self.user_id);
+            }
+        }
+    }
+}
+
+// Global in-flight request limit with priority tiers. High-priority requests
+// may use every permit; normal and low ones are capped to a share of the pool
+// so interactive traffic always has headroom. Low-priority requests are shed
+// immediately instead of queuing.
+
+pub const PRIORITY_HEADER: &str = "X-Priority";
+
+/// Probes and scrapes must answer even when the server is saturated.
+pub const UNLIMITED_PATHS: [&str; 5] =
+    ["/mcp/ping", "/mcp/health", "/mcp/readyz", "/mcp/status", "/mcp/metrics"];
+
+/// Suggested client back-off when a request is shed.
+const SHED_RETRY_AFTER_SECS: u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+impl Priority {
+    /// Priority from `X-Priority`; missing or unrecognized values are normal.
+    /// `high` is only honored from trusted proxies, like `X-Tenant-Id`.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let value = req
+            .headers()
+            .get(PRIORITY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.trim().to_ascii_lowercase());
+        match value.as_deref() {
+            Some("high") if crate::middleware::peer_is_trusted(req) => Priority::High,
+            Some("low") => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        }
+    }
+}
+
+struct PermitPools {
+    total: Arc<Semaphore>,
+    normal: Arc<Semaphore>,
+    low: Arc<Semaphore>,
+}
+
+pub struct RequestLimiter {
+    pools: Option<PermitPools>,
+    queue_timeout: Duration,
+}
+
+/// Held for the lifetime of an admitted request.
+pub struct RequestPermit {
+    _tier: Option<OwnedSemaphorePermit>,
+    _total: OwnedSemaphorePermit,
+}
+
+impl RequestLimiter {
+    /// `max_concurrent` of zero disables the limit. Normal requests may use
+    /// three quarters of the permits and low ones half.
+    pub fn new(max_concurrent: usize, queue_timeout: Duration) -> Self {
+        let pools = (max_concurrent > 0).then(|| PermitPools {
+            total: Arc::new(Semaphore::new(max_concurrent)),
+            normal: Arc::new(Semaphore::new((max_concurrent * 3 / 4).max(1))),
+            low: Arc::new(Semaphore::new((max_concurrent / 2).max(1))),
+        });
+        Self {
+            pools,
+            queue_timeout,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.pools.is_some()
+    }
+
+    /// Admit a request. Low priority never waits; the others queue for up
+    /// to the configured timeout.
+    pub async fn acquire(&self, priority: Priority) -> Result<Option<RequestPermit>, McpError> {
+        let pools = match &self.pools {
+            Some(pools) => pools,
+            None => return Ok(None),
+        };
+
+        let permit = if priority == Priority::Low {
+            let tier = pools.low.clone().try_acquire_owned().ok();
+            let total = tier
+                .as_ref()
+                .and_then(|_| pools.total.clone().try_acquire_owned().ok());
+            tier.zip(total).map(|(tier, total)| RequestPermit {
+                _tier: Some(tier),
+                _total: total,
+            })
+        } else {
+            tokio::time::timeout(self.queue_timeout, async {
+                // High priority skips the tier pool and may use every permit
+                let tier = match priority {
+                    Priority::Normal => Some(pools.normal.clone().acquire_owned().await.ok()?),
+                    _ => None,
+                };
+                let total = pools.total.clone().acquire_owned().await.ok()?;
+                Some(RequestPermit {
+                    _tier: tier,
+                    _total: total,
+                })
+            })
+            .await
+            .ok()
+            .flatten()
+        };
+
+        metrics::observe_request_priority(priority.as_str(), permit.is_some());
+        match permit {
+            Some(permit) => Ok(Some(permit)),
+            None => Err(McpError::TooManyRequests {
+                message: "Server is at capacity; try again shortly".to_string(),
+                retry_after_secs: SHED_RETRY_AFTER_SECS,
+            }),
+        }
+    }
+}
+
+/// Middleware applying the request limiter, when one is configured.
+pub async fn limit_requests<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let limiter = req
+        .app_data::<web::Data<Arc<RequestLimiter>>>()
+        .filter(|limiter| limiter.is_enabled() && !UNLIMITED_PATHS.contains(&req.path()))
+        .cloned();
+    let limiter = match limiter {
+        Some(limiter) => limiter,
+        None => return next.call(req).await.map(ServiceResponse::map_into_left_body),
+    };
+
+    let priority = Priority::from_request(req.request());
+    match limiter.acquire(priority).await {
+        Ok(_permit) => next.call(req).await.map(ServiceResponse::map_into_left_body),
+        Err(err) => Ok(req.error_response(err).map_into_right_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn priority_from(peer: &str, value: &str) -> Priority {
+        let mut config = Config::for_tests();
+        config.trusted_proxies = vec!["10.0.0.0/8".parse().unwrap()];
+        let req = actix_web::test::TestRequest::default()
+            .app_data(web::Data::new(config))
+            .peer_addr(peer.parse().unwrap())
+            .insert_header((PRIORITY_HEADER, value))
+            .to_http_request();
+        Priority::from_request(&req)
+    }
+
+    #[test]
+    fn high_priority_is_only_honored_from_trusted_peers() {
+        assert_eq!(priority_from("10.1.2.3:4000", "high"), Priority::High);
+        assert_eq!(priority_from("192.168.1.5:4000", "high"), Priority::Normal);
+        assert_eq!(priority_from("192.168.1.5:4000", "low"), Priority::Low);
+    }
+
+    #[test]
+    fn user_task_limit_rejects_calls_over_the_cap() {
+        let limits = UserTaskLimits::new(1);
+        let first = limits.try_acquire("alice").unwrap();
+        assert!(matches!(
+            limits.try_acquire("alice"),
+            Err(McpError::TooManyRequests { .. })
+        ));
+        assert!(limits.try_acquire("bob").is_ok());
+        drop(first);
+        assert!(limits.try_acquire("alice").is_ok());
+    }
+
+    #[test]
+    fn zero_user_task_limit_is_unlimited() {
+        let limits = UserTaskLimits::new(0);
+        assert!(!limits.is_enabled());
+        let guards: Vec<_> = (0..16).map(|_| limits.try_acquire("alice").unwrap()).collect();
+        assert_eq!(guards.len(), 16);
+        assert!(limits.permits.lock().unwrap().is_empty());
+    }
+}
@@ -1,30 +1,658 @@
+use ipnet::IpNet;
+use std::collections::HashMap;
+use crate::allowlist::HostAllowlist;
+use crate::errors::McpError;
+use crate::hedging::HedgePolicy;
+use crate::pricing::{self, PriceTable};
+use crate::prompts;
+use crate::query_builder::QueryStrategy;
+use crate::rate_limit::TenantRateLimits;
+use crate::validation::FieldLimits;
+use crate::sampling::StoreSamplingPolicy;
+use crate::secrets::SecretSource;
+use crate::teams::TeamDirectory;
+use crate::wire::WireFormat;
 use std::env;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Languages the retrieval pipeline handles well, unless KNOWN_LANGUAGES
+/// overrides them.
+pub(crate) const DEFAULT_KNOWN_LANGUAGES: [&str; 20] = [
+    "c", "cpp", "csharp", "css", "go", "html", "java", "javascript", "json", "kotlin",
+    "markdown", "php", "python", "ruby", "rust", "shell", "sql", "swift", "typescript", "yaml",
+];
+
+/// Shape of error response bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// `{ "error", "code", "status" }`
+    Simple,
+    /// RFC 7807 `application/problem+json`
+    Problem,
+}
+
+/// How the user dimension is recorded. Raw user ids are never used as a
+/// label; they are either dropped or hashed into a fixed number of buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserLabelMode {
+    Drop,
+    Hash { buckets: u64 },
+}
+
+impl UserLabelMode {
+    pub fn parse(mode: &str, buckets: u64) -> Self {
+        match mode {
+            "hash" => UserLabelMode::Hash {
+                buckets: buckets.max(1),
+            },
+            _ => UserLabelMode::Drop,
+        }
+    }
+}
+
+/// What `submit_task` does when the intelligence service omits `session_id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingSessionPolicy {
+    /// Use the request's session id, or a new one if it had none
+    Reuse,
+    /// Always start a new session id
+    Generate,
+    /// Fail the request with an internal error
+    Fail,
+}
+
+/// Per-endpoint kill switches. Disabled endpoints stay routed and answer
+/// 503 so clients can tell them apart from unknown paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointToggles {
+    pub context_fetch: bool,
+    pub memory_log: bool,
+    pub memory_import: bool,
+    pub memory_export: bool,
+    pub memory_consolidate: bool,
+    pub memory_get: bool,
+    pub task_submit: bool,
+}
+
+impl EndpointToggles {
+    fn from_vars(vars: &EnvVars) -> Self {
+        Self {
+            context_fetch: env_bool(vars, "ENABLE_CONTEXT_FETCH", true),
+            memory_log: env_bool(vars, "ENABLE_MEMORY_LOG", true),
+            memory_import: env_bool(vars, "ENABLE_MEMORY_IMPORT", true),
+            memory_export: env_bool(vars, "ENABLE_MEMORY_EXPORT", true),
+            memory_consolidate: env_bool(vars, "ENABLE_MEMORY_CONSOLIDATE", true),
+            memory_get: env_bool(vars, "ENABLE_MEMORY_GET", true),
+            task_submit: env_bool(vars, "ENABLE_TASK_SUBMIT", true),
+        }
+    }
+
+    fn entries(&self) -> [(&'static str, bool); 7] {
+        [
+            ("/mcp/context/fetch", self.context_fetch),
+            ("/mcp/memory/log", self.memory_log),
+            ("/mcp/memory/import", self.memory_import),
+            ("/mcp/memory/export", self.memory_export),
+            ("/mcp/memory/consolidate", self.memory_consolidate),
+            ("/mcp/memory/{id}", self.memory_get),
+            ("/mcp/task/submit", self.task_submit),
+        ]
+    }
+
+    /// Switchable endpoints that are currently on.
+    pub fn enabled(&self) -> Vec<&'static str> {
+        self.entries()
+            .into_iter()
+            .filter(|(_, on)| *on)
+            .map(|(endpoint, _)| endpoint)
+            .collect()
+    }
+
+    /// Reject calls to a switched-off endpoint. Endpoints without a
+    /// switch are always enabled.
+    pub fn ensure_enabled(&self, endpoint: &str) -> Result<(), McpError> {
+        let disabled = self
+            .entries()
+            .iter()
+            .any(|(path, on)| *path == endpoint && !on);
+        if disabled {
+            return Err(McpError::ServiceUnavailable("endpoint disabled".to_string()));
+        }
+        Ok(())
+    }
+}
 
 #[derive(Clone)]
 pub struct Config {
     pub port: u16,
     pub memory_service_url: String,
     pub intelligence_service_url: String,
+    pub memory_region_urls: Vec<String>,
+    pub memory_replica_urls: Vec<String>,
+    pub hedge_policy: HedgePolicy,
+    pub memory_service_path_prefix: String,
+    pub intelligence_service_path_prefix: String,
+    pub memory_service_encoding: WireFormat,
+    pub intelligence_service_encoding: WireFormat,
     pub jwt_secret: String,
+    pub jwt_clock_skew_secs: u64,
+    pub pagination_secret: String,
     pub database_url: Option<String>,
+    pub infer_memory_outcome: bool,
+    pub read_only: bool,
+    pub admin_token: Option<String>,
+    pub circuit_failure_threshold: u32,
+    pub circuit_reset_timeout_secs: u64,
+    pub degrade_on_open_circuit: bool,
+    pub memory_service_required: bool,
+    pub intelligence_service_required: bool,
+    pub trusted_proxies: Vec<IpNet>,
+    pub downstream_allowlist: HostAllowlist,
+    pub memory_import_concurrency: usize,
+    pub memory_import_max_bytes: usize,
+    pub max_memory_tags: usize,
+    pub max_tag_length: usize,
+    pub memory_dedup_window_secs: u64,
+    pub task_dedup_window_secs: u64,
+    pub max_memories_per_user: u64,
+    pub memory_confidence_scale: f64,
+    pub normalize_timestamps: bool,
+    pub encrypt_memory_content: bool,
+    pub memory_encryption_key: Option<String>,
+    pub memory_count_cache_secs: u64,
+    pub memory_event_webhook_url: Option<String>,
+    pub consolidate_cooldown_secs: u64,
+    pub metrics_user_label: UserLabelMode,
+    pub metrics_flush_interval_ms: u64,
+    pub downstream_timeout_ms: u64,
+    pub downstream_connect_timeout_ms: u64,
+    pub max_downstream_response_bytes: usize,
+    pub max_request_timeout_ms: u64,
+    pub request_deadline_ms: u64,
+    pub health_check_timeout_ms: u64,
+    pub health_ping_interval_secs: u64,
+    pub prompt_templates: HashMap<String, String>,
+    pub task_fallback_message: Option<String>,
+    pub session_lock_timeout_ms: u64,
+    pub max_tasks_per_user: usize,
+    pub max_concurrent_requests: usize,
+    pub request_queue_timeout_ms: u64,
+    pub rate_limits: TenantRateLimits,
+    pub log_sample_rate: u64,
+    pub slow_request_ms: u64,
+    pub cors_max_age_secs: usize,
+    pub tag_boost_factor: f32,
+    pub stale_context_threshold_secs: i64,
+    pub context_cache_size: usize,
+    pub context_cache_stale_secs: u64,
+    pub known_languages: Vec<String>,
+    pub infer_language_from_path: bool,
+    pub context_cache_max_age_secs: u64,
+    pub min_context_confidence: f32,
+    pub allow_metrics_reset: bool,
+    pub store_sampling: StoreSamplingPolicy,
+    pub error_format: ErrorFormat,
+    pub missing_session_policy: MissingSessionPolicy,
+    pub context_query_strategy: QueryStrategy,
+    pub field_limits: FieldLimits,
+    pub model_prices: PriceTable,
+    pub intelligence_model: String,
+    pub teams: TeamDirectory,
+    pub memory_multi_user_search: bool,
+    pub endpoints: EndpointToggles,
 }
 
 impl Config {
     pub fn from_env() -> Self {
-        dotenv::dotenv().ok();
-        
+        let vars = EnvVars::load();
+        let secrets = Secrets::resolve(&vars);
+        Self::from_vars(&vars, secrets)
+    }
+
+    /// Read the configuration again for a reload. Secrets are carried over
+    /// from `current` instead of being resolved again, so secret files and
+    /// commands are only read at startup.
+    pub fn reload_from_env(current: &Config) -> Self {
+        Self::from_vars(&EnvVars::load(), Secrets::from_config(current))
+    }
+
+    /// Defaults only, ignoring the process environment and `.env`, for tests.
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        let vars = EnvVars {
+            dotenv: HashMap::new(),
+            process_env: false,
+        };
+        Self::from_vars(&vars, Secrets::default())
+    }
+
+    fn from_vars(vars: &EnvVars, secrets: Secrets) -> Self {
         Self {
-            port: env::var("PORT")
+            port: vars.var("PORT")
                 .unwrap_or_else(|_| "7000".to_string())
                 .parse()
                 .expect("PORT must be a valid u16"),
-            memory_service_url: env::var("MEMORY_SERVICE_URL")
+            memory_service_url: vars.var("MEMORY_SERVICE_URL")
                 .unwrap_or_else(|_| "http://memory:8001".to_string()),
-            intelligence_service_url: env::var("INTELLIGENCE_SERVICE_URL")
+            intelligence_service_url: vars.var("INTELLIGENCE_SERVICE_URL")
                 .unwrap_or_else(|_| "http://intelligence:8000".to_string()),
-            jwt_secret: env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "your-secret-key".to_string()),
-            database_url: env::var("DATABASE_URL").ok(),
+            memory_region_urls: env_list(vars, "MEMORY_REGION_URLS"),
+            memory_replica_urls: env_list(vars, "MEMORY_REPLICA_URLS"),
+            hedge_policy: HedgePolicy {
+                operations: env_list(vars, "HEDGED_OPERATIONS"),
+                percentile: vars.var("HEDGE_DELAY_PERCENTILE")
+                    .unwrap_or_else(|_| "95".to_string())
+                    .parse()
+                    .expect("HEDGE_DELAY_PERCENTILE must be a valid f64"),
+                initial_delay: Duration::from_millis(
+                    vars.var("HEDGE_INITIAL_DELAY_MS")
+                        .unwrap_or_else(|_| "50".to_string())
+                        .parse()
+                        .expect("HEDGE_INITIAL_DELAY_MS must be a valid u64"),
+                ),
+                max_in_flight: vars.var("HEDGE_MAX_IN_FLIGHT")
+                    .unwrap_or_else(|_| "10".to_string())
+                    .parse()
+                    .expect("HEDGE_MAX_IN_FLIGHT must be a valid usize"),
+            },
+            memory_service_path_prefix: path_prefix(vars, "MEMORY_SERVICE_PATH_PREFIX"),
+            intelligence_service_path_prefix: path_prefix(vars, "INTELLIGENCE_SERVICE_PATH_PREFIX"),
+            memory_service_encoding: WireFormat::parse(
+                &vars.var("MEMORY_SERVICE_ENCODING").unwrap_or_else(|_| "json".to_string()),
+                "MEMORY_SERVICE_ENCODING",
+            ),
+            intelligence_service_encoding: WireFormat::parse(
+                &vars.var("INTELLIGENCE_SERVICE_ENCODING").unwrap_or_else(|_| "json".to_string()),
+                "INTELLIGENCE_SERVICE_ENCODING",
+            ),
+            jwt_secret: secrets.jwt_secret,
+            jwt_clock_skew_secs: vars.var("JWT_CLOCK_SKEW_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("JWT_CLOCK_SKEW_SECS must be a valid u64"),
+            pagination_secret: secrets.pagination_secret,
+            database_url: vars.var("DATABASE_URL").ok(),
+            infer_memory_outcome: env_bool(vars, "INFER_MEMORY_OUTCOME", false),
+            read_only: env_bool(vars, "READ_ONLY", false),
+            admin_token: secrets.admin_token,
+            circuit_failure_threshold: vars.var("CIRCUIT_FAILURE_THRESHOLD")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .expect("CIRCUIT_FAILURE_THRESHOLD must be a valid u32"),
+            circuit_reset_timeout_secs: vars.var("CIRCUIT_RESET_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .expect("CIRCUIT_RESET_TIMEOUT_SECS must be a valid u64"),
+            degrade_on_open_circuit: env_bool(vars, "DEGRADE_ON_OPEN_CIRCUIT", true),
+            memory_service_required: env_bool(vars, "MEMORY_SERVICE_REQUIRED", true),
+            intelligence_service_required: env_bool(vars, "INTELLIGENCE_SERVICE_REQUIRED", true),
+            trusted_proxies: env_list(vars, "TRUSTED_PROXIES")
+                .into_iter()
+                .map(|entry| parse_cidr(&entry))
+                .collect(),
+            downstream_allowlist: HostAllowlist::parse(&env_list(vars, "DOWNSTREAM_ALLOWED_HOSTS")),
+            memory_import_concurrency: vars.var("MEMORY_IMPORT_CONCURRENCY")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .expect("MEMORY_IMPORT_CONCURRENCY must be a valid usize"),
+            memory_import_max_bytes: vars.var("MEMORY_IMPORT_MAX_BYTES")
+                .unwrap_or_else(|_| "10485760".to_string())
+                .parse()
+                .expect("MEMORY_IMPORT_MAX_BYTES must be a valid usize"),
+            max_memory_tags: vars.var("MAX_MEMORY_TAGS")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .expect("MAX_MEMORY_TAGS must be a valid usize"),
+            max_tag_length: vars.var("MAX_TAG_LENGTH")
+                .unwrap_or_else(|_| "64".to_string())
+                .parse()
+                .expect("MAX_TAG_LENGTH must be a valid usize"),
+            memory_dedup_window_secs: vars.var("MEMORY_DEDUP_WINDOW_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .expect("MEMORY_DEDUP_WINDOW_SECS must be a valid u64"),
+            task_dedup_window_secs: vars.var("TASK_DEDUP_WINDOW_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("TASK_DEDUP_WINDOW_SECS must be a valid u64"),
+            max_memories_per_user: vars.var("MAX_MEMORIES_PER_USER")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("MAX_MEMORIES_PER_USER must be a valid u64"),
+            memory_confidence_scale: vars.var("MEMORY_CONFIDENCE_SCALE")
+                .unwrap_or_else(|_| "1.0".to_string())
+                .parse()
+                .expect("MEMORY_CONFIDENCE_SCALE must be a valid f64"),
+            normalize_timestamps: env_bool(vars, "NORMALIZE_TIMESTAMPS", true),
+            encrypt_memory_content: env_bool(vars, "ENCRYPT_MEMORY_CONTENT", false),
+            memory_encryption_key: secrets.memory_encryption_key,
+            memory_count_cache_secs: vars.var("MEMORY_COUNT_CACHE_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("MEMORY_COUNT_CACHE_SECS must be a valid u64"),
+            memory_event_webhook_url: vars.var("MEMORY_EVENT_WEBHOOK_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty()),
+            consolidate_cooldown_secs: vars.var("CONSOLIDATE_COOLDOWN_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .expect("CONSOLIDATE_COOLDOWN_SECS must be a valid u64"),
+            metrics_user_label: UserLabelMode::parse(
+                &vars.var("METRICS_USER_LABEL").unwrap_or_else(|_| "drop".to_string()),
+                vars.var("METRICS_USER_BUCKETS")
+                    .unwrap_or_else(|_| "16".to_string())
+                    .parse()
+                    .expect("METRICS_USER_BUCKETS must be a valid u64"),
+            ),
+            metrics_flush_interval_ms: vars.var("METRICS_FLUSH_INTERVAL_MS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("METRICS_FLUSH_INTERVAL_MS must be a valid u64"),
+            downstream_timeout_ms: vars.var("DOWNSTREAM_TIMEOUT_MS")
+                .unwrap_or_else(|_| "30000".to_string())
+                .parse()
+                .expect("DOWNSTREAM_TIMEOUT_MS must be a valid u64"),
+            downstream_connect_timeout_ms: vars.var("DOWNSTREAM_CONNECT_TIMEOUT_MS")
+                .unwrap_or_else(|_| "2000".to_string())
+                .parse()
+                .expect("DOWNSTREAM_CONNECT_TIMEOUT_MS must be a valid u64"),
+            max_downstream_response_bytes: vars.var("MAX_DOWNSTREAM_RESPONSE_BYTES")
+                .unwrap_or_else(|_| "10485760".to_string())
+                .parse()
+                .expect("MAX_DOWNSTREAM_RESPONSE_BYTES must be a valid usize"),
+            max_request_timeout_ms: vars.var("MAX_REQUEST_TIMEOUT_MS")
+                .unwrap_or_else(|_| "120000".to_string())
+                .parse()
+                .expect("MAX_REQUEST_TIMEOUT_MS must be a valid u64"),
+            request_deadline_ms: vars.var("REQUEST_DEADLINE_MS")
+                .unwrap_or_else(|_| "300000".to_string())
+                .parse()
+                .expect("REQUEST_DEADLINE_MS must be a valid u64"),
+            health_check_timeout_ms: vars.var("HEALTH_CHECK_TIMEOUT_MS")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .expect("HEALTH_CHECK_TIMEOUT_MS must be a valid u64"),
+            health_ping_interval_secs: vars.var("HEALTH_PING_INTERVAL_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("HEALTH_PING_INTERVAL_SECS must be a valid u64"),
+            prompt_templates: prompts::load_templates(
+                vars.var("PROMPT_TEMPLATES_FILE").ok().as_deref(),
+            ),
+            task_fallback_message: vars.var("TASK_FALLBACK_MESSAGE")
+                .ok()
+                .filter(|message| !message.is_empty()),
+            session_lock_timeout_ms: vars.var("SESSION_LOCK_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .expect("SESSION_LOCK_TIMEOUT_MS must be a valid u64"),
+            max_tasks_per_user: vars.var("MAX_TASKS_PER_USER")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("MAX_TASKS_PER_USER must be a valid usize"),
+            max_concurrent_requests: vars.var("MAX_CONCURRENT_REQUESTS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("MAX_CONCURRENT_REQUESTS must be a valid usize"),
+            request_queue_timeout_ms: vars.var("REQUEST_QUEUE_TIMEOUT_MS")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .expect("REQUEST_QUEUE_TIMEOUT_MS must be a valid u64"),
+            rate_limits: TenantRateLimits::parse(
+                vars.var("RATE_LIMIT_RPM")
+                    .unwrap_or_else(|_| "0".to_string())
+                    .parse()
+                    .expect("RATE_LIMIT_RPM must be a valid u32"),
+                &env_list(vars, "TENANT_RATE_LIMITS"),
+            ),
+            log_sample_rate: vars.var("LOG_SAMPLE_RATE")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .expect("LOG_SAMPLE_RATE must be a valid u64"),
+            slow_request_ms: vars.var("SLOW_REQUEST_MS")
+                .unwrap_or_else(|_| "2000".to_string())
+                .parse()
+                .expect("SLOW_REQUEST_MS must be a valid u64"),
+            cors_max_age_secs: vars.var("CORS_MAX_AGE_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .expect("CORS_MAX_AGE_SECS must be a valid usize"),
+            tag_boost_factor: vars.var("TAG_BOOST_FACTOR")
+                .unwrap_or_else(|_| "1.5".to_string())
+                .parse()
+                .expect("TAG_BOOST_FACTOR must be a valid f32"),
+            stale_context_threshold_secs: vars.var("STALE_CONTEXT_THRESHOLD_SECS")
+                .unwrap_or_else(|_| "2592000".to_string())
+                .parse()
+                .expect("STALE_CONTEXT_THRESHOLD_SECS must be a valid i64"),
+            context_cache_size: vars.var("CONTEXT_CACHE_SIZE")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("CONTEXT_CACHE_SIZE must be a valid usize"),
+            context_cache_stale_secs: vars.var("CONTEXT_CACHE_STALE_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .expect("CONTEXT_CACHE_STALE_SECS must be a valid u64"),
+            context_cache_max_age_secs: vars.var("CONTEXT_CACHE_MAX_AGE_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("CONTEXT_CACHE_MAX_AGE_SECS must be a valid u64"),
+            infer_language_from_path: env_bool(vars, "INFER_LANGUAGE_FROM_PATH", true),
+            min_context_confidence: vars.var("MIN_CONTEXT_CONFIDENCE")
+                .unwrap_or_else(|_| "0.0".to_string())
+                .parse()
+                .expect("MIN_CONTEXT_CONFIDENCE must be a valid f32"),
+            known_languages: {
+                let languages = env_list(vars, "KNOWN_LANGUAGES");
+                if languages.is_empty() {
+                    DEFAULT_KNOWN_LANGUAGES.iter().map(|l| l.to_string()).collect()
+                } else {
+                    languages
+                }
+            },
+            allow_metrics_reset: env_bool(vars, "ALLOW_METRICS_RESET", false),
+            store_sampling: StoreSamplingPolicy::parse(
+                &env_list(vars, "MEMORY_STORE_SAMPLE_RATES"),
+                env_bool(vars, "MEMORY_STORE_SAMPLE_DETERMINISTIC", false),
+            ),
+            error_format: match vars.var("ERROR_FORMAT").as_deref() {
+                Ok("problem") => ErrorFormat::Problem,
+                _ => ErrorFormat::Simple,
+            },
+            missing_session_policy: match vars.var("MISSING_SESSION_ID_POLICY").as_deref() {
+                Ok("generate") => MissingSessionPolicy::Generate,
+                Ok("fail") => MissingSessionPolicy::Fail,
+                _ => MissingSessionPolicy::Reuse,
+            },
+            context_query_strategy: QueryStrategy::parse(
+                &vars.var("CONTEXT_QUERY_STRATEGY").unwrap_or_else(|_| "full".to_string()),
+                vars.var("CONTEXT_SNIPPET_LINES")
+                    .unwrap_or_else(|_| "20".to_string())
+                    .parse()
+                    .expect("CONTEXT_SNIPPET_LINES must be a valid usize"),
+            ),
+            field_limits: FieldLimits::parse(&env_list(vars, "FIELD_MAX_LENGTHS")),
+            model_prices: PriceTable::parse(&env_list(vars, "MODEL_PRICES")),
+            intelligence_model: vars
+                .var("INTELLIGENCE_MODEL")
+                .unwrap_or_else(|_| pricing::DEFAULT_MODEL.to_string()),
+            teams: TeamDirectory::parse(&env_list(vars, "TEAM_MEMBERS")),
+            memory_multi_user_search: env_bool(vars, "MEMORY_MULTI_USER_SEARCH", false),
+            endpoints: EndpointToggles::from_vars(vars),
+        }
+    }
+
+    /// Whether a peer may assert identity via `X-User-Id`. With no
+    /// `TRUSTED_PROXIES` configured every peer is trusted.
+    pub fn is_trusted_proxy(&self, peer: IpAddr) -> bool {
+        self.trusted_proxies.is_empty()
+            || self.trusted_proxies.iter().any(|net| net.contains(&peer))
+    }
+}
+
+/// Configuration variables: the process environment, with the `.env` file
+/// filling in keys the environment doesn't set. The process environment is
+/// never modified, so values set by the orchestrator always win, including
+/// on a reload.
+struct EnvVars {
+    dotenv: HashMap<String, String>,
+    /// Read the process environment before `.env`; off only in tests
+    process_env: bool,
+}
+
+impl EnvVars {
+    fn load() -> Self {
+        let dotenv = dotenv::dotenv_iter()
+            .map(|entries| entries.flatten().collect())
+            .unwrap_or_default();
+        Self {
+            dotenv,
+            process_env: true,
+        }
+    }
+
+    fn var(&self, key: &str) -> Result<String, env::VarError> {
+        let process = if self.process_env {
+            env::var(key)
+        } else {
+            Err(env::VarError::NotPresent)
+        };
+        process.or_else(|err| self.dotenv.get(key).cloned().ok_or(err))
+    }
+}
+
+/// Sensitive settings, which may come from files or commands.
+#[cfg_attr(test, derive(Default))]
+struct Secrets {
+    jwt_secret: String,
+    pagination_secret: String,
+    admin_token: Option<String>,
+    memory_encryption_key: Option<String>,
+}
+
+impl Secrets {
+    fn resolve(vars: &EnvVars) -> Self {
+        let jwt_secret =
+            resolve_secret(vars, "JWT_SECRET").unwrap_or_else(|| "your-secret-key".to_string());
+        Self {
+            pagination_secret: resolve_secret(vars, "PAGINATION_SECRET")
+                .unwrap_or_else(|| jwt_secret.clone()),
+            jwt_secret,
+            admin_token: resolve_secret(vars, "ADMIN_TOKEN"),
+            memory_encryption_key: resolve_secret(vars, "MEMORY_ENCRYPTION_KEY"),
         }
     }
+
+    fn from_config(config: &Config) -> Self {
+        Self {
+            jwt_secret: config.jwt_secret.clone(),
+            pagination_secret: config.pagination_secret.clone(),
+            admin_token: config.admin_token.clone(),
+            memory_encryption_key: config.memory_encryption_key.clone(),
+        }
+    }
+}
+
+/// Resolve a sensitive value through its configured `SecretSource`.
+fn resolve_secret(vars: &EnvVars, name: &str) -> Option<String> {
+    SecretSource::from_vars(name, |key| vars.var(key).ok())
+        .resolve()
+        .unwrap_or_else(|err| panic!("{} could not be resolved: {:#}", name, err))
+}
+
+/// Read and validate a downstream path prefix. Empty is allowed; otherwise
+/// it must start with '/', must not end with '/', and may only contain URL
+/// path characters.
+fn path_prefix(vars: &EnvVars, key: &str) -> String {
+    let prefix = vars.var(key).unwrap_or_default().trim().to_string();
+    if prefix.is_empty() {
+        return prefix;
+    }
+    let valid_chars = prefix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '-' | '_' | '.' | '~'));
+    if !prefix.starts_with('/') || prefix.ends_with('/') || prefix.contains("//") || !valid_chars {
+        panic!(
+            "{} must look like /api/v1 (leading '/', no trailing '/'), got '{}'",
+            key, prefix
+        );
+    }
+    prefix
+}
+
+/// Parse a CIDR block, accepting bare addresses as single-host networks.
+fn parse_cidr(entry: &str) -> IpNet {
+    entry
+        .parse::<IpNet>()
+        .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+        .unwrap_or_else(|_| panic!("TRUSTED_PROXIES entry '{}' is not a valid CIDR", entry))
+}
+
+/// Parse a boolean env var ("true"/"1"/"yes"), falling back to `default`.
+fn env_bool(vars: &EnvVars, key: &str, default: bool) -> bool {
+    vars.var(key)
+        .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "true" | "1" | "yes"))
+        .unwrap_or(default)
+}
+
+/// Parse a comma-separated env var into a list, ignoring blank entries.
+fn env_list(vars: &EnvVars, key: &str) -> Vec<String> {
+    vars.var(key)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(dotenv: &[(&str, &str)]) -> EnvVars {
+        EnvVars {
+            dotenv: dotenv
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            process_env: true,
+        }
+    }
+
+    #[test]
+    fn dotenv_fills_only_keys_the_environment_does_not_set() {
+        let process_path = env::var("PATH").expect("PATH is set for tests");
+        let vars = vars(&[
+            ("PATH", "/from/dotenv"),
+            ("MCP_CONFIG_TEST_DOTENV_ONLY", "from-dotenv"),
+        ]);
+        assert_eq!(vars.var("PATH").unwrap(), process_path);
+        assert_eq!(vars.var("MCP_CONFIG_TEST_DOTENV_ONLY").unwrap(), "from-dotenv");
+        assert!(vars.var("MCP_CONFIG_TEST_UNSET").is_err());
+    }
+
+    #[test]
+    fn secrets_resolve_from_dotenv_sources() {
+        let vars = vars(&[
+            ("JWT_SECRET", "jwt"),
+            ("ADMIN_TOKEN_COMMAND", "printf admin"),
+        ]);
+        let secrets = Secrets::resolve(&vars);
+        assert_eq!(secrets.jwt_secret, "jwt");
+        assert_eq!(secrets.pagination_secret, "jwt");
+        assert_eq!(secrets.admin_token.as_deref(), Some("admin"));
+        assert_eq!(secrets.memory_encryption_key, None);
+    }
+
+    #[test]
+    fn user_label_mode_hashes_only_when_asked() {
+        assert_eq!(UserLabelMode::parse("hash", 16), UserLabelMode::Hash { buckets: 16 });
+        assert_eq!(UserLabelMode::parse("hash", 0), UserLabelMode::Hash { buckets: 1 });
+        assert_eq!(UserLabelMode::parse("drop", 16), UserLabelMode::Drop);
+        assert_eq!(UserLabelMode::parse("raw", 16), UserLabelMode::Drop);
+    }
 }
@@ -0,0 +1,90 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::ContextFetchResponse;
+use crate::pagination::SearchCursor;
+
+// Last known good context responses, served only when the memory service is
+// unreachable. Entries older than the stale window are never served. A
+// capacity of zero disables the cache.
+
+pub struct ContextCache {
+    entries: Mutex<HashMap<u64, (Instant, ContextFetchResponse)>>,
+    capacity: usize,
+    stale_window: Duration,
+}
+
+impl ContextCache {
+    pub fn new(capacity: usize, stale_window: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+            stale_window,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn insert(
+        &self,
+        user_id: &str,
+        cursor: &SearchCursor,
+        explain: bool,
+        response: &ContextFetchResponse,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity {
+            entries.retain(|_, (stored_at, _)| now.duration_since(*stored_at) < self.stale_window);
+        }
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (stored_at, _))| *stored_at)
+                .map(|(key, _)| *key);
+            if let Some(key) = oldest {
+                entries.remove(&key);
+            }
+        }
+        entries.insert(cache_key(user_id, cursor, explain), (now, response.clone()));
+    }
+
+    /// Cached response for the same search, if still inside the stale window.
+    pub fn get(
+        &self,
+        user_id: &str,
+        cursor: &SearchCursor,
+        explain: bool,
+    ) -> Option<ContextFetchResponse> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&cache_key(user_id, cursor, explain))
+            .filter(|(stored_at, _)| stored_at.elapsed() < self.stale_window)
+            .map(|(_, response)| response.clone())
+    }
+}
+
+/// Identifies a search independent of when its first page was served.
+fn cache_key(user_id: &str, cursor: &SearchCursor, explain: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    user_id.hash(&mut hasher);
+    cursor.query_hash.hash(&mut hasher);
+    cursor.limit.hash(&mut hasher);
+    cursor.offset.hash(&mut hasher);
+    cursor.boost_tags.hash(&mut hasher);
+    cursor.team_id.hash(&mut hasher);
+    cursor.min_confidence.to_bits().hash(&mut hasher);
+    explain.hash(&mut hasher);
+    hasher.finish()
+}
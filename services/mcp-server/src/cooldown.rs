@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::errors::McpError;
+
+// Per-user cooldown for expensive operations: a user may trigger the
+// operation at most once per interval. A run reserves the user's slot up
+// front, so concurrent requests can't all slip through, and a run that fails
+// gives the slot back so it can be retried straight away. Expired entries are
+// pruned on each reservation so the map only holds users still inside their
+// cooldown.
+
+pub struct UserCooldown {
+    last_run: Mutex<HashMap<String, Instant>>,
+    interval: Duration,
+}
+
+/// A reserved slot. Dropped without `commit`, e.g. after a failed run, it
+/// releases the slot again.
+pub struct CooldownReservation<'a> {
+    cooldown: &'a UserCooldown,
+    user_id: String,
+    reserved_at: Instant,
+    committed: bool,
+}
+
+impl UserCooldown {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            last_run: Mutex::new(HashMap::new()),
+            interval,
+        }
+    }
+
+    /// Take `user_id`'s slot, or report how long they must wait if they ran
+    /// recently or a run is in progress.
+    pub fn reserve(&self, user_id: &str) -> Result<CooldownReservation<'_>, McpError> {
+        let now = Instant::now();
+        let mut last_run = self.last_run.lock().unwrap_or_else(|e| e.into_inner());
+        last_run.retain(|_, at| now.duration_since(*at) < self.interval);
+
+        if let Some(at) = last_run.get(user_id) {
+            let remaining = self.interval - now.duration_since(*at);
+            return Err(McpError::TooManyRequests {
+                message: "Operation was triggered recently; try again later".to_string(),
+                retry_after_secs: remaining.as_secs().max(1),
+            });
+        }
+        last_run.insert(user_id.to_string(), now);
+        Ok(CooldownReservation {
+            cooldown: self,
+            user_id: user_id.to_string(),
+            reserved_at: now,
+            committed: false,
+        })
+    }
+}
+
+impl CooldownReservation<'_> {
+    /// Keep the slot after a successful run, starting the cooldown.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for CooldownReservation<'_> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        let mut last_run = self.cooldown.last_run.lock().unwrap_or_else(|e| e.into_inner());
+        // Only release our own reservation, not one taken after it expired
+        if last_run.get(&self.user_id) == Some(&self.reserved_at) {
+            last_run.remove(&self.user_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_cooling_down(result: Result<CooldownReservation<'_>, McpError>) {
+        match result {
+            Err(McpError::TooManyRequests { retry_after_secs, .. }) => {
+                assert!((1..=60).contains(&retry_after_secs));
+            }
+            Err(other) => panic!("expected TooManyRequests, got {:?}", other),
+            Ok(_) => panic!("expected TooManyRequests, got a reservation"),
+        }
+    }
+
+    #[test]
+    fn only_committed_runs_start_the_cooldown() {
+        let cooldown = UserCooldown::new(Duration::from_secs(60));
+        // A failed run drops its reservation, so the user may retry
+        drop(cooldown.reserve("alice").unwrap());
+
+        cooldown.reserve("alice").unwrap().commit();
+        assert_cooling_down(cooldown.reserve("alice"));
+        assert!(cooldown.reserve("bob").is_ok());
+    }
+
+    #[test]
+    fn a_run_in_progress_holds_the_slot() {
+        let cooldown = UserCooldown::new(Duration::from_secs(60));
+        let running = cooldown.reserve("alice").unwrap();
+        assert_cooling_down(cooldown.reserve("alice"));
+        drop(running);
+        assert!(cooldown.reserve("alice").is_ok());
+    }
+
+    #[test]
+    fn cooldown_expires() {
+        let cooldown = UserCooldown::new(Duration::ZERO);
+        cooldown.reserve("alice").unwrap().commit();
+        assert!(cooldown.reserve("alice").is_ok());
+    }
+}
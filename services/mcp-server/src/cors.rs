@@ -0,0 +1,46 @@
+use actix_cors::Cors;
+use actix_web::http::header;
+
+use crate::config::Config;
+
+// CORS policies per route group. Preflight results are cached by browsers
+// for CORS_MAX_AGE_SECS.
+
+const JSON_ALLOWED_HEADERS: [&str; 10] = [
+    "Authorization",
+    "Content-Type",
+    "X-User-Id",
+    "X-Timeout-Ms",
+    "X-Admin-Token",
+    "X-Request-Id",
+    "X-Api-Version",
+    "X-Priority",
+    "X-Tenant-Id",
+    "X-Debug",
+];
+
+const STREAMING_ALLOWED_HEADERS: [&str; 4] = ["Authorization", "Accept", "X-User-Id", "X-Request-Id"];
+
+/// Policy for the JSON request/response endpoints.
+pub fn json_cors(config: &Config) -> Cors {
+    Cors::default()
+        .allow_any_origin()
+        .allowed_methods(vec!["GET", "POST", "OPTIONS"])
+        .allowed_headers(JSON_ALLOWED_HEADERS)
+        .expose_headers(vec!["X-Request-Id", "X-Api-Version"])
+        .max_age(config.cors_max_age_secs)
+}
+
+/// Policy for streaming download endpoints: GET only, and exposes the
+/// headers browsers need to save the stream as a file.
+pub fn streaming_cors(config: &Config) -> Cors {
+    Cors::default()
+        .allow_any_origin()
+        .allowed_methods(vec!["GET", "OPTIONS"])
+        .allowed_headers(STREAMING_ALLOWED_HEADERS)
+        .expose_headers(vec![
+            header::CONTENT_DISPOSITION.as_str(),
+            "X-Request-Id",
+        ])
+        .max_age(config.cors_max_age_secs)
+}
@@ -0,0 +1,153 @@
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderValue},
+    middleware::Next,
+    web, Error, HttpMessage,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::downstream::DownstreamCalls;
+use crate::state::RuntimeState;
+
+// Per-request downstream waterfall for debugging. An admin sending
+// `X-Debug: true` gets a `debug` section appended to JSON responses, listing
+// every downstream call the request made. The calls are the ones the access
+// log already records in the request extensions; without the header and a
+// valid admin token the response is passed through untouched.
+
+const DEBUG_HEADER: &str = "X-Debug";
+
+pub async fn debug_trace(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let requested = req
+        .headers()
+        .get(DEBUG_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    if !requested {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let authorized = req
+        .app_data::<web::Data<Arc<RuntimeState>>>()
+        .map(|state| crate::middleware::require_admin(req.request(), &state.config()).is_ok())
+        .unwrap_or(false);
+    if !authorized {
+        log::warn!("Ignoring X-Debug without a valid admin token on {}", req.path());
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let calls = req.extensions().get::<DownstreamCalls>().cloned();
+    let res = next.call(req).await?;
+    let calls = match calls {
+        Some(calls) if is_json(&res) => calls,
+        _ => return Ok(res.map_into_boxed_body()),
+    };
+
+    // Buffering is fine here: only non-streaming JSON bodies get this far
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(|e| {
+            let e: Box<dyn std::error::Error> = e.into();
+            actix_web::error::ErrorInternalServerError(e.to_string())
+        })?;
+
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(mut object)) => {
+            let downstream_calls: Vec<Value> = calls
+                .snapshot()
+                .iter()
+                .map(|call| {
+                    json!({
+                        "service": call.service,
+                        "url": call.url,
+                        "status": call.status,
+                        "latency_ms": call.latency.as_secs_f64() * 1000.0,
+                    })
+                })
+                .collect();
+            object.insert("debug".to_string(), json!({ "downstream_calls": downstream_calls }));
+            res.headers_mut()
+                .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+            Value::Object(object).to_string().into_bytes().into()
+        }
+        _ => bytes,
+    };
+
+    Ok(ServiceResponse::new(req, res.set_body(body).map_into_boxed_body()))
+}
+
+fn is_json<B>(res: &ServiceResponse<B>) -> bool {
+    res.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with("application/json") || value.starts_with("application/problem+json"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use actix_web::{middleware::from_fn, test, App, HttpResponse};
+
+    async fn json_handler() -> HttpResponse {
+        HttpResponse::Ok().json(json!({ "ok": true }))
+    }
+
+    async fn record_calls(
+        req: ServiceRequest,
+        next: Next<impl MessageBody + 'static>,
+    ) -> Result<ServiceResponse<impl MessageBody>, Error> {
+        req.extensions_mut().insert(DownstreamCalls::default());
+        next.call(req).await
+    }
+
+    /// Body of a debug request carrying `token`, with `runtime_token` as the
+    /// live admin token and `startup_token` as the one in the startup config.
+    async fn debug_body(
+        token: &str,
+        runtime_token: Option<&str>,
+        startup_token: Option<&str>,
+    ) -> Value {
+        let mut runtime = Config::for_tests();
+        runtime.admin_token = runtime_token.map(str::to_string);
+        let mut startup = Config::for_tests();
+        startup.admin_token = startup_token.map(str::to_string);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(RuntimeState::new(&runtime))))
+                .app_data(web::Data::new(startup))
+                .wrap(from_fn(debug_trace))
+                .wrap(from_fn(record_calls))
+                .route("/mcp/test", web::get().to(json_handler)),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/mcp/test")
+            .insert_header((DEBUG_HEADER, "true"))
+            .insert_header(("X-Admin-Token", token))
+            .to_request();
+        test::call_and_read_body_json(&app, req).await
+    }
+
+    #[actix_web::test]
+    async fn admin_debug_requests_get_the_downstream_calls() {
+        let body = debug_body("secret", Some("secret"), None).await;
+        assert_eq!(body["ok"], true);
+        assert_eq!(body["debug"]["downstream_calls"], json!([]));
+    }
+
+    #[actix_web::test]
+    async fn admin_check_uses_the_live_config() {
+        let body = debug_body("stale", Some("secret"), Some("stale")).await;
+        assert_eq!(body, json!({ "ok": true }));
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::MemoryStoreRequest;
+
+// Store-time deduplication. Remembers a content hash of each recent store
+// per user, so the same interaction logged again inside the window is
+// answered with the existing memory id instead of a second copy.
+
+/// Upper bound on remembered hashes, in case the window is long.
+const MAX_ENTRIES: usize = 10_000;
+
+pub struct StoreDedup {
+    recent: Mutex<HashMap<u64, (Instant, String)>>,
+    window: Duration,
+}
+
+impl StoreDedup {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            recent: Mutex::new(HashMap::new()),
+            window,
+        }
+    }
+
+    /// Id of an identical memory stored inside the window, if any.
+    pub fn existing(&self, hash: u64) -> Option<String> {
+        if self.window.is_zero() {
+            return None;
+        }
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent
+            .get(&hash)
+            .filter(|(stored_at, _)| stored_at.elapsed() < self.window)
+            .map(|(_, memory_id)| memory_id.clone())
+    }
+
+    pub fn remember(&self, hash: u64, memory_id: &str) {
+        if self.window.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.len() >= MAX_ENTRIES {
+            recent.retain(|_, (stored_at, _)| now.duration_since(*stored_at) < self.window);
+        }
+        if recent.len() < MAX_ENTRIES {
+            recent.insert(hash, (now, memory_id.to_string()));
+        }
+    }
+}
+
+/// Hash of what makes two stores the same interaction. Tags and tier are
+/// left out so re-tagged repeats still count as duplicates.
+pub fn content_hash(user_id: &str, request: &MemoryStoreRequest) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    user_id.hash(&mut hasher);
+    request.memory_type.hash(&mut hasher);
+    request.input_context.hash(&mut hasher);
+    request.output_response.hash(&mut hasher);
+    request.outcome.hash(&mut hasher);
+    hasher.finish()
+}
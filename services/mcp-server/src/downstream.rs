@@ -0,0 +1,101 @@
+use reqwest::{RequestBuilder, Response};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::allowlist::HostAllowlist;
+use crate::errors::McpError;
+use crate::metrics;
+
+// Per-request record of downstream calls.
+// The access-log middleware opens a task-local scope around each request;
+// service clients append to it when they call a downstream. Calls made
+// outside a request scope (background tasks, streamed bodies) are not kept.
+
+#[derive(Debug, Clone)]
+pub struct DownstreamCall {
+    pub service: &'static str,
+    pub url: String,
+    pub status: Option<u16>,
+    pub latency: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DownstreamCalls(Arc<Mutex<Vec<DownstreamCall>>>);
+
+impl DownstreamCalls {
+    pub fn record(&self, call: DownstreamCall) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(call);
+    }
+
+    pub fn snapshot(&self) -> Vec<DownstreamCall> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Total latency per service, e.g. `memory_service:12ms,intelligence_service:840ms`.
+    pub fn latency_summary(&self) -> String {
+        let mut totals: Vec<(&'static str, Duration)> = Vec::new();
+        for call in self.snapshot() {
+            match totals.iter_mut().find(|(service, _)| *service == call.service) {
+                Some((_, total)) => *total += call.latency,
+                None => totals.push((call.service, call.latency)),
+            }
+        }
+        if totals.is_empty() {
+            return "-".to_string();
+        }
+        totals
+            .iter()
+            .map(|(service, total)| format!("{}:{}ms", service, total.as_millis()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+tokio::task_local! {
+    pub static DOWNSTREAM_CALLS: DownstreamCalls;
+}
+
+static ALLOWLIST: OnceLock<HostAllowlist> = OnceLock::new();
+
+pub fn configure_allowlist(allowlist: HostAllowlist) {
+    if ALLOWLIST.set(allowlist).is_err() {
+        log::warn!("Downstream allowlist already configured");
+    }
+}
+
+/// Whether `url` may be called under the configured allowlist.
+pub fn is_allowed(url: &str) -> bool {
+    ALLOWLIST
+        .get()
+        .map(|allowlist| allowlist.is_allowed(url))
+        .unwrap_or(true)
+}
+
+/// Send a downstream request, recording its status and latency against the
+/// current request scope if there is one. Targets outside the allowlist are
+/// refused without being contacted.
+pub async fn send(
+    service: &'static str,
+    url: &str,
+    builder: RequestBuilder,
+) -> Result<Response, McpError> {
+    if !is_allowed(url) {
+        log::warn!("Refused {} request to non-allowlisted target {}", service, url);
+        metrics::observe_downstream_rejection(service);
+        return Err(McpError::InternalError(format!(
+            "{} target is not in the downstream allowlist",
+            service
+        )));
+    }
+
+    let start = Instant::now();
+    let result = builder.send().await;
+    let call = DownstreamCall {
+        service,
+        url: url.to_string(),
+        status: result.as_ref().ok().map(|response| response.status().as_u16()),
+        latency: start.elapsed(),
+    };
+    let _ = DOWNSTREAM_CALLS.try_with(|calls| calls.record(call));
+    result.map_err(McpError::from)
+}
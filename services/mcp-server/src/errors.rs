@@ -1,13 +1,26 @@
-use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::{JsonPayloadError, ResponseError},
+    http::{header, StatusCode},
+    middleware::Next,
+    web, HttpRequest, HttpResponse,
+};
+
+use crate::config::{Config, ErrorFormat};
 use std::fmt;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum McpError {
     ServiceUnavailable(String),
     InvalidRequest(String),
     Unauthorized(String),
+    Forbidden(String),
     NotFound(String),
     InternalError(String),
+    GatewayTimeout(String),
+    TooManyRequests { message: String, retry_after_secs: u64 },
 }
 
 impl fmt::Display for McpError {
@@ -16,27 +29,95 @@ impl fmt::Display for McpError {
             McpError::ServiceUnavailable(msg) => write!(f, "Service unavailable: {}", msg),
             McpError::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
             McpError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
+            McpError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             McpError::NotFound(msg) => write!(f, "Not found: {}", msg),
             McpError::InternalError(msg) => write!(f, "Internal error: {}", msg),
+            McpError::GatewayTimeout(msg) => write!(f, "Gateway timeout: {}", msg),
+            McpError::TooManyRequests { message, .. } => {
+                write!(f, "Too many requests: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for McpError {}
+
+impl McpError {
+    /// Stable machine-readable code for clients to branch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            McpError::ServiceUnavailable(_) => "service_unavailable",
+            McpError::InvalidRequest(_) => "invalid_request",
+            McpError::Unauthorized(_) => "unauthorized",
+            McpError::Forbidden(_) => "forbidden",
+            McpError::NotFound(_) => "not_found",
+            McpError::InternalError(_) => "internal_error",
+            McpError::GatewayTimeout(_) => "gateway_timeout",
+            McpError::TooManyRequests { .. } => "too_many_requests",
+        }
+    }
+
+    /// Whether the same call may succeed if repeated later. Downstream
+    /// outages (timeouts, connection failures, open circuits) and rate limits
+    /// are transient; bad input, auth failures and internal errors are not.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            McpError::ServiceUnavailable(_)
+                | McpError::GatewayTimeout(_)
+                | McpError::TooManyRequests { .. }
+        )
+    }
+
+    /// How long to wait before retrying, when the error says.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            McpError::TooManyRequests { retry_after_secs, .. } => {
+                Some(Duration::from_secs(*retry_after_secs))
+            }
+            _ => None,
         }
     }
 }
 
+impl McpError {
+    /// RFC 7807 representation of this error for `instance`.
+    pub fn problem_details(&self, instance: &str) -> serde_json::Value {
+        let status = self.status_code();
+        serde_json::json!({
+            "type": format!("urn:mcp:error:{}", self.code()),
+            "title": status.canonical_reason().unwrap_or("Error"),
+            "status": status.as_u16(),
+            "detail": self.to_string(),
+            "instance": instance,
+            "code": self.code(),
+        })
+    }
+}
+
 impl ResponseError for McpError {
     fn status_code(&self) -> StatusCode {
         match self {
             McpError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
             McpError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
             McpError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            McpError::Forbidden(_) => StatusCode::FORBIDDEN,
             McpError::NotFound(_) => StatusCode::NOT_FOUND,
             McpError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            McpError::GatewayTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            McpError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
         let error_message = self.to_string();
-        HttpResponse::build(self.status_code()).json(serde_json::json!({
+        let mut builder = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = self.retry_after() {
+            builder.insert_header((header::RETRY_AFTER, retry_after.as_secs().to_string()));
+        }
+        builder.json(serde_json::json!({
             "error": error_message,
+            "code": self.code(),
             "status": self.status_code().as_u16(),
         }))
     }
@@ -44,7 +125,15 @@ impl ResponseError for McpError {
 
 impl From<reqwest::Error> for McpError {
     fn from(err: reqwest::Error) -> Self {
-        McpError::ServiceUnavailable(err.to_string())
+        // Connect failures (including connect timeouts) are checked first:
+        // reqwest also flags a connect timeout as a timeout.
+        if err.is_connect() {
+            McpError::ServiceUnavailable(format!("connection failed: {}", err))
+        } else if err.is_timeout() {
+            McpError::ServiceUnavailable(format!("timed out waiting for response: {}", err))
+        } else {
+            McpError::ServiceUnavailable(err.to_string())
+        }
     }
 }
 
@@ -53,3 +142,139 @@ impl From<serde_json::Error> for McpError {
         McpError::InvalidRequest(err.to_string())
     }
 }
+
+/// Error handler for `web::JsonConfig` so malformed bodies produce our
+/// standard error shape instead of actix's plain-text default.
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let detail = match err {
+        JsonPayloadError::Deserialize(e) => e.to_string(),
+        other => other.to_string(),
+    };
+    McpError::InvalidRequest(detail).into()
+}
+
+/// Rewrite `McpError` responses as `application/problem+json` when
+/// `ERROR_FORMAT=problem` or the client's Accept header asks for it. The
+/// original error stays attached so the access log still sees its code.
+pub async fn problem_details(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let configured = req
+        .app_data::<web::Data<Config>>()
+        .map(|config| config.error_format == ErrorFormat::Problem)
+        .unwrap_or(false);
+    let requested = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map(|accept| accept.contains("application/problem+json"))
+        .unwrap_or(false);
+    let instance = req.path().to_string();
+
+    let mut res = next.call(req).await?;
+    if !(configured || requested) {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let problem = res
+        .response()
+        .error()
+        .and_then(|err| err.as_error::<McpError>())
+        .map(|err| err.problem_details(&instance).to_string());
+
+    match problem {
+        Some(body) => {
+            res.headers_mut().insert(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/problem+json"),
+            );
+            Ok(res.map_body(|_, _| BoxBody::new(body)))
+        }
+        None => Ok(res.map_into_boxed_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    async fn echo(body: web::Json<serde_json::Value>) -> HttpResponse {
+        HttpResponse::Ok().json(body.into_inner())
+    }
+
+    async fn post_json(payload: &'static str) -> (StatusCode, serde_json::Value) {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+                .route("/echo", web::post().to(echo)),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload(payload)
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        let status = res.status();
+        (status, test::read_body_json(res).await)
+    }
+
+    #[actix_web::test]
+    async fn malformed_json_gets_the_standard_error_shape() {
+        let (status, body) = post_json("{\"file_path\": ").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+        assert_eq!(body["status"], 400);
+        assert!(body["error"].as_str().unwrap().starts_with("Invalid request: "));
+    }
+
+    #[actix_web::test]
+    async fn well_formed_json_passes_through() {
+        let (status, body) = post_json("{\"file_path\": \"a.rs\"}").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["file_path"], "a.rs");
+    }
+
+    #[test]
+    fn transient_errors_are_retryable() {
+        let retryable = [
+            McpError::ServiceUnavailable("down".to_string()),
+            McpError::GatewayTimeout("slow".to_string()),
+            McpError::TooManyRequests { message: "busy".to_string(), retry_after_secs: 3 },
+        ];
+        for err in &retryable {
+            assert!(err.is_retryable(), "{} should be retryable", err);
+        }
+
+        let permanent = [
+            McpError::InvalidRequest("bad".to_string()),
+            McpError::Unauthorized("who".to_string()),
+            McpError::Forbidden("no".to_string()),
+            McpError::NotFound("gone".to_string()),
+            McpError::InternalError("bug".to_string()),
+        ];
+        for err in &permanent {
+            assert!(!err.is_retryable(), "{} should not be retryable", err);
+        }
+    }
+
+    #[test]
+    fn only_rate_limits_say_when_to_retry() {
+        let limited = McpError::TooManyRequests { message: "busy".to_string(), retry_after_secs: 3 };
+        assert_eq!(limited.retry_after(), Some(Duration::from_secs(3)));
+        assert_eq!(McpError::ServiceUnavailable("down".to_string()).retry_after(), None);
+    }
+
+    #[test]
+    fn rate_limit_responses_carry_retry_after() {
+        let limited = McpError::TooManyRequests { message: "busy".to_string(), retry_after_secs: 3 };
+        let res = limited.error_response();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[header::RETRY_AFTER], "3");
+
+        let res = McpError::ServiceUnavailable("down".to_string()).error_response();
+        assert!(res.headers().get(header::RETRY_AFTER).is_none());
+    }
+}
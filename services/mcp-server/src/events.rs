@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::downstream;
+use crate::metrics;
+
+// Optional notifications for other systems when a memory is stored.
+// Events are POSTed to a webhook in a background task, so publishing never
+// adds latency to the request and failures are only logged and counted.
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+pub struct MemoryStoredEvent {
+    pub event: &'static str,
+    pub user_id: String,
+    pub memory_id: String,
+    pub action: String,
+    pub timestamp: String,
+}
+
+pub struct EventPublisher {
+    client: Client,
+    webhook_url: Option<String>,
+}
+
+impl EventPublisher {
+    pub fn new(webhook_url: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            webhook_url,
+        }
+    }
+
+    /// Publish a `memory.stored` event in the background. A no-op when no
+    /// webhook is configured.
+    pub fn memory_stored(&self, user_id: &str, memory_id: &str, action: &str) {
+        let url = match &self.webhook_url {
+            Some(url) => url.clone(),
+            None => return,
+        };
+        let event = MemoryStoredEvent {
+            event: "memory.stored",
+            user_id: user_id.to_string(),
+            memory_id: memory_id.to_string(),
+            action: action.to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        let builder = self.client.post(&url).timeout(WEBHOOK_TIMEOUT).json(&event);
+
+        actix_web::rt::spawn(async move {
+            let result = downstream::send("event_webhook", &url, builder).await;
+            let published = match result {
+                Ok(response) if response.status().is_success() => true,
+                Ok(response) => {
+                    log::warn!("Memory event webhook returned status: {}", response.status());
+                    false
+                }
+                Err(err) => {
+                    log::warn!("Failed to publish memory event: {}", err);
+                    false
+                }
+            };
+            metrics::observe_event_publish(published);
+        });
+    }
+}
@@ -0,0 +1,163 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+
+use crate::errors::McpError;
+
+// Field-level encryption of memory content, so the memory service only ever
+// stores ciphertext. Values are sealed with XChaCha20-Poly1305 under a random
+// nonce and tagged `enc:v1:<base64(nonce || ciphertext)>`. The field name is
+// bound as associated data, so a ciphertext can't be moved to another field.
+// Values without the tag are plaintext written before encryption was enabled.
+
+const MARKER: &str = "enc:";
+const VERSION: &str = "v1:";
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+#[derive(Clone)]
+pub struct FieldCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl FieldCipher {
+    /// Build a cipher from a base64-encoded 32-byte key.
+    pub fn from_base64(key: &str) -> Result<Self, String> {
+        let key = STANDARD
+            .decode(key.trim())
+            .map_err(|e| format!("is not valid base64: {}", e))?;
+        if key.len() != KEY_LEN {
+            return Err(format!("must be {} bytes, got {}", KEY_LEN, key.len()));
+        }
+        Ok(Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key)),
+        })
+    }
+
+    pub fn encrypt(&self, field: &str, plaintext: &str) -> Result<String, McpError> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: plaintext.as_bytes(),
+            aad: field.as_bytes(),
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| McpError::InternalError(format!("Failed to encrypt {}", field)))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}{}", MARKER, VERSION, STANDARD.encode(sealed)))
+    }
+
+    /// Decrypt a value written by `encrypt`. Plaintext is returned as is.
+    pub fn decrypt(&self, field: &str, value: &str) -> Result<String, McpError> {
+        let tagged = match value.strip_prefix(MARKER) {
+            Some(tagged) => tagged,
+            None => return Ok(value.to_string()),
+        };
+        let failed = |reason: &str| {
+            McpError::InternalError(format!("Failed to decrypt {}: {}", field, reason))
+        };
+        let encoded = tagged
+            .strip_prefix(VERSION)
+            .ok_or_else(|| failed("unsupported encryption version"))?;
+        let sealed = STANDARD
+            .decode(encoded)
+            .map_err(|_| failed("invalid base64"))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(failed("value too short"));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: field.as_bytes(),
+        };
+        let plaintext = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| failed("wrong key or tampered value"))?;
+        String::from_utf8(plaintext).map_err(|_| failed("not valid UTF-8"))
+    }
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(MARKER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    const OTHER_KEY: &str = "BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=";
+
+    fn cipher(key: &str) -> FieldCipher {
+        FieldCipher::from_base64(key).unwrap()
+    }
+
+    #[test]
+    fn round_trips_with_version_marker() {
+        let cipher = cipher(KEY);
+        let sealed = cipher.encrypt("input_context", "fn main() {}").unwrap();
+        assert!(sealed.starts_with("enc:v1:"));
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains("fn main"));
+        assert_eq!(cipher.decrypt("input_context", &sealed).unwrap(), "fn main() {}");
+    }
+
+    #[test]
+    fn nonces_are_random() {
+        let cipher = cipher(KEY);
+        let first = cipher.encrypt("input_context", "same").unwrap();
+        let second = cipher.encrypt("input_context", "same").unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn plaintext_passes_through() {
+        let cipher = cipher(KEY);
+        assert!(!is_encrypted("File: a.rs"));
+        assert_eq!(cipher.decrypt("input_context", "File: a.rs").unwrap(), "File: a.rs");
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let cipher = cipher(KEY);
+        let sealed = cipher.encrypt("input_context", "secret").unwrap();
+        let mut bytes = STANDARD.decode(sealed.trim_start_matches("enc:v1:")).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        let tampered = format!("enc:v1:{}", STANDARD.encode(bytes));
+        assert!(cipher.decrypt("input_context", &tampered).is_err());
+    }
+
+    #[test]
+    fn ciphertext_is_bound_to_its_field() {
+        let cipher = cipher(KEY);
+        let sealed = cipher.encrypt("input_context", "secret").unwrap();
+        assert!(cipher.decrypt("output_response", &sealed).is_err());
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let sealed = cipher(KEY).encrypt("input_context", "secret").unwrap();
+        assert!(cipher(OTHER_KEY).decrypt("input_context", &sealed).is_err());
+    }
+
+    #[test]
+    fn unknown_version_and_short_values_are_rejected() {
+        let cipher = cipher(KEY);
+        assert!(cipher.decrypt("input_context", "enc:v2:AAAA").is_err());
+        assert!(cipher.decrypt("input_context", "enc:v1:AAAA").is_err());
+        assert!(cipher.decrypt("input_context", "enc:v1:not base64!").is_err());
+    }
+
+    #[test]
+    fn key_must_be_32_bytes_of_base64() {
+        assert!(FieldCipher::from_base64("AAAAAAAAAAAAAAAAAAAAAA==").is_err());
+        assert!(FieldCipher::from_base64("not base64!").is_err());
+        assert!(FieldCipher::from_base64(KEY).is_ok());
+    }
+}
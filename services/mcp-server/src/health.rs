@@ -0,0 +1,77 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::time::MissedTickBehavior;
+
+use crate::metrics;
+use crate::services::{IntelligenceServiceClient, MemoryServiceClient};
+
+// Background downstream health monitoring. A loop pings both downstreams on
+// a fixed interval and caches the result, so readiness probes answer
+// without waiting on downstream latency. Only state changes are logged.
+
+#[derive(Debug, Clone, Default)]
+pub struct HealthSnapshot {
+    /// `None` until the first ping completes
+    pub memory_service: Option<bool>,
+    pub intelligence_service: Option<bool>,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+pub struct HealthMonitor {
+    snapshot: Mutex<HealthSnapshot>,
+}
+
+impl HealthMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> HealthSnapshot {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Ping both downstreams every `interval`, forever. The first ping runs
+    /// immediately.
+    pub async fn run(
+        self: Arc<Self>,
+        memory_client: Arc<MemoryServiceClient>,
+        intelligence_client: Arc<IntelligenceServiceClient>,
+        interval: Duration,
+    ) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let (memory_ok, intelligence_ok) =
+                tokio::join!(memory_client.health_check(), intelligence_client.health_check());
+            self.record(memory_ok, intelligence_ok);
+        }
+    }
+
+    fn record(&self, memory_ok: bool, intelligence_ok: bool) {
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        log_transition("memory_service", snapshot.memory_service, memory_ok);
+        log_transition("intelligence_service", snapshot.intelligence_service, intelligence_ok);
+        metrics::observe_downstream_health("memory_service", memory_ok);
+        metrics::observe_downstream_health("intelligence_service", intelligence_ok);
+        *snapshot = HealthSnapshot {
+            memory_service: Some(memory_ok),
+            intelligence_service: Some(intelligence_ok),
+            checked_at: Some(Utc::now()),
+        };
+    }
+}
+
+fn log_transition(service: &str, previous: Option<bool>, up: bool) {
+    match (previous, up) {
+        (Some(previous), up) if previous == up => {}
+        (_, true) => log::info!("Downstream {} is up", service),
+        (_, false) => log::warn!("Downstream {} is down", service),
+    }
+}
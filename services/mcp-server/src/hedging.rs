@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures::future::{select, Either};
+
+use crate::errors::McpError;
+use crate::metrics;
+
+// Request hedging for latency-critical reads. When the first attempt hasn't
+// answered within the configured percentile of recent latencies, a second
+// attempt goes to another replica. The first success wins; dropping the
+// other future cancels its HTTP request. The percentile is recomputed as
+// latencies are recorded, so picking the delay is a single atomic load.
+
+/// Latencies remembered per hedger for the percentile.
+const WINDOW: usize = 200;
+/// Latencies needed before the percentile replaces the initial delay.
+const MIN_SAMPLES: usize = 20;
+/// Latencies recorded between recomputations of the percentile.
+const RECOMPUTE_EVERY: usize = 10;
+
+/// Which reads may be hedged and how eagerly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HedgePolicy {
+    /// Opted-in operations (`search`, `get`); empty disables hedging
+    pub operations: Vec<String>,
+    /// Latency percentile (0-100) the first attempt gets before a hedge
+    pub percentile: f64,
+    /// Hedge delay until enough latencies have been observed
+    pub initial_delay: Duration,
+    /// Hedged attempts allowed in flight at once
+    pub max_in_flight: usize,
+}
+
+impl HedgePolicy {
+    pub fn disabled() -> Self {
+        Self {
+            operations: Vec::new(),
+            percentile: 95.0,
+            initial_delay: Duration::from_millis(50),
+            max_in_flight: 0,
+        }
+    }
+}
+
+pub struct Hedger {
+    policy: HedgePolicy,
+    latencies: Mutex<Latencies>,
+    /// Current hedge delay in microseconds
+    delay_micros: AtomicU64,
+    in_flight: AtomicUsize,
+}
+
+struct Latencies {
+    window: VecDeque<Duration>,
+    since_recompute: usize,
+}
+
+/// Releases a hedge slot when the hedged call finishes or is dropped.
+struct HedgeSlot<'a>(&'a AtomicUsize);
+
+impl Drop for HedgeSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Hedger {
+    pub fn new(policy: HedgePolicy) -> Self {
+        Self {
+            delay_micros: AtomicU64::new(policy.initial_delay.as_micros() as u64),
+            policy,
+            latencies: Mutex::new(Latencies {
+                window: VecDeque::with_capacity(WINDOW),
+                since_recompute: 0,
+            }),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self, operation: &str) -> bool {
+        self.policy.max_in_flight > 0 && self.policy.operations.iter().any(|op| op == operation)
+    }
+
+    /// How long the first attempt runs alone before a hedge is sent.
+    pub fn delay(&self) -> Duration {
+        Duration::from_micros(self.delay_micros.load(Ordering::Relaxed))
+    }
+
+    fn record(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap_or_else(|e| e.into_inner());
+        if latencies.window.len() == WINDOW {
+            latencies.window.pop_front();
+        }
+        latencies.window.push_back(latency);
+        latencies.since_recompute += 1;
+        if latencies.window.len() < MIN_SAMPLES || latencies.since_recompute < RECOMPUTE_EVERY {
+            return;
+        }
+        latencies.since_recompute = 0;
+
+        let mut samples: Vec<Duration> = latencies.window.iter().copied().collect();
+        let rank = (self.policy.percentile.clamp(0.0, 100.0) / 100.0) * (samples.len() - 1) as f64;
+        let (_, delay, _) = samples.select_nth_unstable(rank.round() as usize);
+        self.delay_micros.store(delay.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn try_acquire(&self) -> Option<HedgeSlot<'_>> {
+        let acquired = self
+            .in_flight
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                (current < self.policy.max_in_flight).then_some(current + 1)
+            })
+            .is_ok();
+        acquired.then(|| HedgeSlot(&self.in_flight))
+    }
+
+    /// Run `attempt` against `primary`, sending a second attempt to the
+    /// target `backup` picks if `operation` is opted in and the first is
+    /// slower than `delay()`. `backup` is only called when a hedge is sent.
+    pub async fn run<'a, T, F, Fut>(
+        &self,
+        operation: &'static str,
+        primary: &'a str,
+        backup: impl FnOnce() -> &'a str,
+        attempt: F,
+    ) -> Result<T, McpError>
+    where
+        F: Fn(&'a str) -> Fut,
+        Fut: Future<Output = Result<T, McpError>>,
+    {
+        if !self.is_enabled(operation) {
+            return attempt(primary).await;
+        }
+
+        let started = Instant::now();
+        let mut first = Box::pin(attempt(primary));
+        if let Ok(result) = tokio::time::timeout(self.delay(), &mut first).await {
+            if result.is_ok() {
+                self.record(started.elapsed());
+            }
+            return result;
+        }
+
+        let _slot = match self.try_acquire() {
+            Some(slot) => slot,
+            None => {
+                metrics::MCP_HEDGED_REQUESTS_TOTAL
+                    .with_label_values(&[operation, "capped"])
+                    .inc();
+                let result = first.await;
+                if result.is_ok() {
+                    self.record(started.elapsed());
+                }
+                return result;
+            }
+        };
+        metrics::MCP_HEDGED_REQUESTS_TOTAL
+            .with_label_values(&[operation, "hedged"])
+            .inc();
+
+        let second = Box::pin(attempt(backup()));
+        let (result, hedge_won) = match select(first, second).await {
+            Either::Left((Ok(value), _)) => (Ok(value), false),
+            Either::Right((Ok(value), _)) => (Ok(value), true),
+            // One attempt failed; the other one's answer stands
+            Either::Left((Err(_), second)) => (second.await, true),
+            Either::Right((Err(_), first)) => (first.await, false),
+        };
+        if result.is_ok() {
+            self.record(started.elapsed());
+            if hedge_won {
+                metrics::MCP_HEDGED_REQUESTS_TOTAL
+                    .with_label_values(&[operation, "won"])
+                    .inc();
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    fn policy(operations: &[&str]) -> HedgePolicy {
+        HedgePolicy {
+            operations: operations.iter().map(|op| op.to_string()).collect(),
+            percentile: 50.0,
+            initial_delay: Duration::from_millis(20),
+            max_in_flight: 1,
+        }
+    }
+
+    #[test]
+    fn delay_starts_at_the_initial_delay_then_follows_the_percentile() {
+        let hedger = Hedger::new(policy(&["search"]));
+        for _ in 0..MIN_SAMPLES - 1 {
+            hedger.record(Duration::from_millis(100));
+        }
+        assert_eq!(hedger.delay(), Duration::from_millis(20));
+
+        hedger.record(Duration::from_millis(100));
+        assert_eq!(hedger.delay(), Duration::from_millis(100));
+
+        // The median only moves once enough new latencies are in
+        for _ in 0..MIN_SAMPLES + RECOMPUTE_EVERY {
+            hedger.record(Duration::from_millis(10));
+        }
+        assert_eq!(hedger.delay(), Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn fast_answers_never_pick_a_backup() {
+        let hedger = Hedger::new(policy(&["search"]));
+        let picked = AtomicBool::new(false);
+        let result = hedger
+            .run(
+                "search",
+                "primary",
+                || {
+                    picked.store(true, Ordering::Relaxed);
+                    "replica"
+                },
+                |url| async move { Ok::<_, McpError>(url) },
+            )
+            .await;
+        assert_eq!(result.unwrap(), "primary");
+        assert!(!picked.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn operations_not_opted_in_never_pick_a_backup() {
+        let hedger = Hedger::new(policy(&["get"]));
+        let picked = AtomicBool::new(false);
+        let result = hedger
+            .run(
+                "search",
+                "primary",
+                || {
+                    picked.store(true, Ordering::Relaxed);
+                    "replica"
+                },
+                |url| async move {
+                    tokio::time::sleep(Duration::from_millis(60)).await;
+                    Ok::<_, McpError>(url)
+                },
+            )
+            .await;
+        assert_eq!(result.unwrap(), "primary");
+        assert!(!picked.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn slow_primary_is_hedged_to_the_backup() {
+        let hedger = Hedger::new(policy(&["search"]));
+        let result = hedger
+            .run("search", "primary", || "replica", |url| async move {
+                if url == "primary" {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+                Ok::<_, McpError>(url)
+            })
+            .await;
+        assert_eq!(result.unwrap(), "replica");
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+// File extension to language inference, for requests that omit `language`.
+// Names match the default KNOWN_LANGUAGES so inferred languages are
+// recognized without extra configuration.
+
+const EXTENSIONS: [(&str, &str); 36] = [
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("cs", "csharp"),
+    ("css", "css"),
+    ("go", "go"),
+    ("htm", "html"),
+    ("html", "html"),
+    ("java", "java"),
+    ("js", "javascript"),
+    ("jsx", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("json", "json"),
+    ("kt", "kotlin"),
+    ("kts", "kotlin"),
+    ("md", "markdown"),
+    ("markdown", "markdown"),
+    ("php", "php"),
+    ("py", "python"),
+    ("pyi", "python"),
+    ("rb", "ruby"),
+    ("rs", "rust"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("sql", "sql"),
+    ("swift", "swift"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("mts", "typescript"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+];
+
+/// Language for `file_path` by its extension, ignoring case. Paths without
+/// an extension or with an unlisted one yield `None`.
+pub fn from_path(file_path: &str) -> Option<&'static str> {
+    let extension = Path::new(file_path).extension()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, language)| *language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_KNOWN_LANGUAGES;
+
+    #[test]
+    fn extensions_map_to_languages_ignoring_case() {
+        assert_eq!(from_path("src/main.rs"), Some("rust"));
+        assert_eq!(from_path("App.TSX"), Some("typescript"));
+        assert_eq!(from_path("include/util.h"), Some("c"));
+        assert_eq!(from_path("/home/me/.config/app.yml"), Some("yaml"));
+        assert_eq!(from_path("archive.tar.py"), Some("python"));
+    }
+
+    #[test]
+    fn unknown_or_missing_extensions_yield_none() {
+        assert_eq!(from_path("Makefile"), None);
+        assert_eq!(from_path("notes.txt"), None);
+        assert_eq!(from_path(".bashrc"), None);
+        assert_eq!(from_path(""), None);
+    }
+
+    #[test]
+    fn every_inferred_language_is_known_by_default() {
+        for (extension, language) in EXTENSIONS {
+            assert!(
+                DEFAULT_KNOWN_LANGUAGES.contains(&language),
+                "{} maps to {}, which is not in DEFAULT_KNOWN_LANGUAGES",
+                extension,
+                language
+            );
+        }
+    }
+
+    #[test]
+    fn extensions_are_listed_once() {
+        for (index, (extension, _)) in EXTENSIONS.iter().enumerate() {
+            assert!(
+                !EXTENSIONS[index + 1..].iter().any(|(other, _)| other == extension),
+                "{} is listed twice",
+                extension
+            );
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Request log sampling.
+// Routine success logs are emitted for 1 in `sample_rate` requests, while
+// failed and slow requests are always logged when they complete.
+
+pub struct LogSampler {
+    sample_rate: u64,
+    slow_threshold: Duration,
+    counter: AtomicU64,
+}
+
+impl LogSampler {
+    pub fn new(sample_rate: u64, slow_threshold: Duration) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1),
+            slow_threshold,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    pub fn sample(&self) -> bool {
+        self.sample_rate == 1 || self.counter.fetch_add(1, Ordering::Relaxed) % self.sample_rate == 0
+    }
+
+    pub fn is_slow(&self, elapsed: Duration) -> bool {
+        elapsed >= self.slow_threshold
+    }
+
+    /// Level of a request's completion line: failures and slow requests are
+    /// always warned about, routine successes are sampled at info.
+    pub fn outcome_level(&self, status: u16, elapsed: Duration) -> Option<log::Level> {
+        if status >= 400 || self.is_slow(elapsed) {
+            Some(log::Level::Warn)
+        } else if self.sample() {
+            Some(log::Level::Info)
+        } else {
+            None
+        }
+    }
+}
+
+static SAMPLER: OnceLock<LogSampler> = OnceLock::new();
+
+pub fn configure(sampler: LogSampler) {
+    if SAMPLER.set(sampler).is_err() {
+        log::warn!("Request log sampler already configured");
+    }
+}
+
+/// Whether a routine log line should be emitted for this request.
+pub fn sample() -> bool {
+    SAMPLER.get().map(LogSampler::sample).unwrap_or(true)
+}
+
+/// Level for the access-log line of a completed request, or `None` when a
+/// routine success is sampled out.
+pub fn outcome_level(status: u16, elapsed: Duration) -> Option<log::Level> {
+    match SAMPLER.get() {
+        Some(sampler) => sampler.outcome_level(status, elapsed),
+        None if status >= 400 => Some(log::Level::Warn),
+        None => Some(log::Level::Info),
+    }
+}
+
+macro_rules! sampled_info {
+    ($($arg:tt)+) => {
+        if $crate::logging::sample() {
+            log::info!($($arg)+);
+        }
+    };
+}
+
+pub(crate) use sampled_info;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampler_keeps_one_in_sample_rate() {
+        let sampler = LogSampler::new(3, Duration::from_secs(1));
+        let kept = (0..9).filter(|_| sampler.sample()).count();
+        assert_eq!(kept, 3);
+    }
+
+    #[test]
+    fn zero_sample_rate_keeps_everything() {
+        let sampler = LogSampler::new(0, Duration::from_secs(1));
+        assert!((0..5).all(|_| sampler.sample()));
+    }
+
+    #[test]
+    fn failures_and_slow_requests_bypass_sampling() {
+        let sampler = LogSampler::new(1000, Duration::from_millis(500));
+        let fast = Duration::from_millis(10);
+        assert_eq!(sampler.outcome_level(200, fast), Some(log::Level::Info));
+        assert_eq!(sampler.outcome_level(200, fast), None);
+        assert_eq!(sampler.outcome_level(503, fast), Some(log::Level::Warn));
+        assert_eq!(
+            sampler.outcome_level(200, Duration::from_millis(600)),
+            Some(log::Level::Warn)
+        );
+    }
+
+    #[test]
+    fn requests_at_the_threshold_are_slow() {
+        let sampler = LogSampler::new(1, Duration::from_millis(500));
+        assert!(!sampler.is_slow(Duration::from_millis(499)));
+        assert!(sampler.is_slow(Duration::from_millis(500)));
+    }
+}
@@ -1,17 +1,60 @@
+mod access_log;
+mod allowlist;
+mod api_version;
+mod batch;
+mod circuit;
+mod concurrency;
 mod config;
+mod context_cache;
+mod cooldown;
+mod cors;
+mod dedup;
+mod debug_trace;
+mod downstream;
 mod metrics;
 mod models;
+mod pagination;
+mod pricing;
+mod prompts;
+mod quota;
+mod query_builder;
+mod ranking;
+mod rate_limit;
+mod reload;
+mod sampling;
 mod routes;
 mod services;
 mod middleware;
 mod errors;
+mod field_crypto;
+mod events;
+mod health;
+mod hedging;
+mod languages;
+mod logging;
+mod secrets;
+mod state;
+mod task_dedup;
+mod teams;
+mod validation;
+mod wire;
 
-use actix_web::{web, App, HttpServer, middleware::Logger};
-use actix_cors::Cors;
+use actix_web::{web, App, HttpServer, middleware::from_fn};
 use std::sync::Arc;
+use std::time::Duration;
 
+use concurrency::{RequestLimiter, SessionLocks, UserTaskLimits};
+use context_cache::ContextCache;
+use health::HealthMonitor;
+use cooldown::UserCooldown;
+use dedup::StoreDedup;
+use events::EventPublisher;
+use quota::MemoryQuota;
+use rate_limit::RateLimiter;
 use config::Config;
 use services::{MemoryServiceClient, IntelligenceServiceClient};
+use state::RuntimeState;
+use task_dedup::TaskDedup;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -24,29 +67,166 @@ async fn main() -> std::io::Result<()> {
     log::info!("Memory Service: {}", config.memory_service_url);
     log::info!("Intelligence Service: {}", config.intelligence_service_url);
     
+    metrics::configure_user_label(config.metrics_user_label);
+    metrics::configure_batching(config.metrics_flush_interval_ms > 0);
+    logging::configure(logging::LogSampler::new(
+        config.log_sample_rate,
+        Duration::from_millis(config.slow_request_ms),
+    ));
+
+    // Refuse to start against downstream targets outside the allowlist
+    let configured_urls = std::iter::once(&config.memory_service_url)
+        .chain(config.memory_region_urls.iter())
+        .chain(config.memory_replica_urls.iter())
+        .chain(std::iter::once(&config.intelligence_service_url))
+        .chain(config.memory_event_webhook_url.iter());
+    for url in configured_urls {
+        if !config.downstream_allowlist.is_allowed(url) {
+            panic!("Downstream URL {} is not in DOWNSTREAM_ALLOWED_HOSTS", url);
+        }
+    }
+    downstream::configure_allowlist(config.downstream_allowlist.clone());
+
+    let content_cipher = config.memory_encryption_key.as_deref().map(|key| {
+        field_crypto::FieldCipher::from_base64(key)
+            .unwrap_or_else(|err| panic!("MEMORY_ENCRYPTION_KEY {}", err))
+    });
+    if config.encrypt_memory_content && content_cipher.is_none() {
+        panic!("ENCRYPT_MEMORY_CONTENT requires MEMORY_ENCRYPTION_KEY");
+    }
+    // The memory service searches embeddings of the content, which would be
+    // embeddings of ciphertext
+    if config.encrypt_memory_content && config.endpoints.context_fetch {
+        panic!("ENCRYPT_MEMORY_CONTENT requires ENABLE_CONTEXT_FETCH=false");
+    }
+    if !config.hedge_policy.operations.is_empty() && config.memory_replica_urls.is_empty() {
+        panic!("HEDGED_OPERATIONS requires MEMORY_REPLICA_URLS");
+    }
+
     // Create service clients
-    let memory_client = Arc::new(MemoryServiceClient::new(&config.memory_service_url));
-    let intelligence_client = Arc::new(IntelligenceServiceClient::new(&config.intelligence_service_url));
+    let circuit_reset_timeout = Duration::from_secs(config.circuit_reset_timeout_secs);
+    let downstream_timeout = Duration::from_millis(config.downstream_timeout_ms);
+    let health_check_timeout = Duration::from_millis(config.health_check_timeout_ms);
+    let connect_timeout = Duration::from_millis(config.downstream_connect_timeout_ms);
+    let memory_client = Arc::new(
+        MemoryServiceClient::new(&config.memory_service_url)
+            .with_path_prefix(&config.memory_service_path_prefix)
+            .with_wire_format(config.memory_service_encoding)
+            .with_regions(config.memory_region_urls.clone())
+            .with_hedging(config.hedge_policy.clone(), config.memory_replica_urls.clone())
+            .with_circuit_breaker(config.circuit_failure_threshold, circuit_reset_timeout)
+            .with_connect_timeout(connect_timeout)
+            .with_timeout(downstream_timeout)
+            .with_health_timeout(health_check_timeout)
+            .with_max_response_bytes(config.max_downstream_response_bytes)
+            .with_confidence_scale(config.memory_confidence_scale)
+            .with_timestamp_normalization(config.normalize_timestamps)
+            .with_content_encryption(content_cipher, config.encrypt_memory_content),
+    );
+    let intelligence_client = Arc::new(
+        IntelligenceServiceClient::new(&config.intelligence_service_url)
+            .with_path_prefix(&config.intelligence_service_path_prefix)
+            .with_wire_format(config.intelligence_service_encoding)
+            .with_circuit_breaker(config.circuit_failure_threshold, circuit_reset_timeout)
+            .with_connect_timeout(connect_timeout)
+            .with_timeout(downstream_timeout)
+            .with_health_timeout(health_check_timeout)
+            .with_max_response_bytes(config.max_downstream_response_bytes),
+    );
     
+    // Keep a cached view of downstream health for readiness probes
+    let health_monitor = Arc::new(HealthMonitor::new());
+    actix_web::rt::spawn(health_monitor.clone().run(
+        memory_client.clone(),
+        intelligence_client.clone(),
+        Duration::from_secs(config.health_ping_interval_secs.max(1)),
+    ));
+
+    let runtime_state = Arc::new(RuntimeState::new(&config));
+    if config.allow_metrics_reset {
+        log::warn!("ALLOW_METRICS_RESET is enabled; do not use this in production");
+    }
+    if runtime_state.is_read_only() {
+        log::warn!("Starting in read-only maintenance mode");
+    }
+
+    let session_locks = Arc::new(SessionLocks::new(Duration::from_millis(
+        config.session_lock_timeout_ms,
+    )));
+    let context_cache = Arc::new(ContextCache::new(
+        config.context_cache_size,
+        Duration::from_secs(config.context_cache_stale_secs),
+    ));
+    let store_dedup = Arc::new(StoreDedup::new(Duration::from_secs(
+        config.memory_dedup_window_secs,
+    )));
+    let task_dedup = Arc::new(TaskDedup::new(Duration::from_secs(
+        config.task_dedup_window_secs,
+    )));
+    let memory_quota = Arc::new(MemoryQuota::new(
+        config.max_memories_per_user,
+        Duration::from_secs(config.memory_count_cache_secs),
+    ));
+    let event_publisher = Arc::new(EventPublisher::new(config.memory_event_webhook_url.clone()));
+    let request_limiter = Arc::new(RequestLimiter::new(
+        config.max_concurrent_requests,
+        Duration::from_millis(config.request_queue_timeout_ms),
+    ));
+    let rate_limiter = Arc::new(RateLimiter::new(config.rate_limits.clone()));
+    actix_web::rt::spawn(reload::reload_on_sighup(
+        runtime_state.clone(),
+        rate_limiter.clone(),
+    ));
+    let user_task_limits = Arc::new(UserTaskLimits::new(config.max_tasks_per_user));
+    let consolidate_cooldown = Arc::new(UserCooldown::new(Duration::from_secs(
+        config.consolidate_cooldown_secs,
+    )));
+
+    if config.metrics_flush_interval_ms > 0 {
+        let flush_interval = Duration::from_millis(config.metrics_flush_interval_ms);
+        actix_web::rt::spawn(async move {
+            let mut ticker = tokio::time::interval(flush_interval);
+            loop {
+                ticker.tick().await;
+                metrics::flush_batched();
+            }
+        });
+    }
+
     let bind_address = ("0.0.0.0", config.port);
     
     // Start HTTP server
     HttpServer::new(move || {
-        // Configure CORS
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header()
-            .max_age(3600);
-        
         App::new()
-            .wrap(Logger::default())
-            .wrap(cors)
+            .wrap(from_fn(concurrency::limit_requests))
+            .wrap(from_fn(rate_limit::rate_limit))
+            .wrap(from_fn(middleware::request_deadline))
+            .wrap(from_fn(errors::problem_details))
+            .wrap(from_fn(debug_trace::debug_trace))
+            .wrap(from_fn(access_log::access_log))
+            .app_data(web::JsonConfig::default().error_handler(errors::json_error_handler))
+            .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::new(runtime_state.clone()))
+            .app_data(web::Data::new(health_monitor.clone()))
+            .app_data(web::Data::new(session_locks.clone()))
+            .app_data(web::Data::new(context_cache.clone()))
+            .app_data(web::Data::new(store_dedup.clone()))
+            .app_data(web::Data::new(task_dedup.clone()))
+            .app_data(web::Data::new(memory_quota.clone()))
+            .app_data(web::Data::new(event_publisher.clone()))
+            .app_data(web::Data::new(request_limiter.clone()))
+            .app_data(web::Data::new(rate_limiter.clone()))
+            .app_data(web::Data::new(user_task_limits.clone()))
+            .app_data(web::Data::new(consolidate_cooldown.clone()))
             .app_data(web::Data::new(memory_client.clone()))
             .app_data(web::Data::new(intelligence_client.clone()))
-            .configure(routes::configure_routes)
+            .configure(|cfg| routes::configure_routes(cfg, &config))
     })
     .bind(bind_address)?
     .run()
-    .await
+    .await?;
+
+    // Buffered request metrics must not be lost with the process
+    metrics::flush_batched();
+    Ok(())
 }
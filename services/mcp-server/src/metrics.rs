@@ -1,7 +1,16 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
+use arc_swap::ArcSwap;
 use lazy_static::lazy_static;
-use prometheus::{self, Encoder, HistogramVec, IntCounterVec, TextEncoder, register_histogram_vec, register_int_counter_vec};
+use prometheus::core::{Collector, Desc};
+use prometheus::local::{LocalHistogramVec, LocalIntCounterVec};
+use prometheus::proto::{MetricFamily, MetricType};
+use prometheus::{self, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, TextEncoder, register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec};
+
+use crate::config::UserLabelMode;
 
 lazy_static! {
     pub static ref MCP_REQUESTS_TOTAL: IntCounterVec = register_int_counter_vec!(
@@ -18,9 +27,183 @@ lazy_static! {
         vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
     )
     .expect("failed to register mcp_request_duration_seconds metric");
+
+    pub static ref MCP_CONTEXT_CONFIDENCE: ResettableHistogram = {
+        let histogram = ResettableHistogram::new(
+            HistogramOpts::new(
+                "mcp_context_confidence",
+                "Confidence score of memory items served as context",
+            )
+            .buckets(vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0]),
+        )
+        .expect("failed to create mcp_context_confidence metric");
+        prometheus::register(Box::new(histogram.clone()))
+            .expect("failed to register mcp_context_confidence metric");
+        histogram
+    };
+
+    pub static ref MCP_USER_REQUESTS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "mcp_user_requests_total",
+        "MCP requests per endpoint and hashed user bucket",
+        &["endpoint", "user_bucket"]
+    )
+    .expect("failed to register mcp_user_requests_total metric");
+
+    pub static ref MCP_MEMORY_STORE_SAMPLING_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "mcp_memory_store_sampling_total",
+        "Memory log sampling decisions by action",
+        &["action", "decision"]
+    )
+    .expect("failed to register mcp_memory_store_sampling_total metric");
+
+    pub static ref MCP_MEMORIES_EXPORTED_TOTAL: IntCounter = register_int_counter!(
+        "mcp_memories_exported_total",
+        "Total memory records streamed out by /mcp/memory/export"
+    )
+    .expect("failed to register mcp_memories_exported_total metric");
+
+    pub static ref MCP_MEMORY_DEDUP_HITS_TOTAL: IntCounter = register_int_counter!(
+        "mcp_memory_dedup_hits_total",
+        "Memory logs skipped as duplicates of a recent store"
+    )
+    .expect("failed to register mcp_memory_dedup_hits_total metric");
+
+    pub static ref MCP_TASK_DEDUP_HITS_TOTAL: IntCounter = register_int_counter!(
+        "mcp_task_dedup_hits_total",
+        "Task submissions answered from an identical recent submission"
+    )
+    .expect("failed to register mcp_task_dedup_hits_total metric");
+
+    pub static ref MCP_CONTEXT_FILTERED_TOTAL: IntCounter = register_int_counter!(
+        "mcp_context_filtered_total",
+        "Context memories dropped for falling below the confidence cutoff"
+    )
+    .expect("failed to register mcp_context_filtered_total metric");
+
+    pub static ref MCP_MEMORY_QUOTA_REJECTIONS_TOTAL: IntCounter = register_int_counter!(
+        "mcp_memory_quota_rejections_total",
+        "Memory logs rejected because the user reached their memory quota"
+    )
+    .expect("failed to register mcp_memory_quota_rejections_total metric");
+
+    pub static ref MCP_USER_TASKS_IN_FLIGHT: IntGaugeVec = register_int_gauge_vec!(
+        "mcp_user_tasks_in_flight",
+        "In-flight task submissions per hashed user bucket",
+        &["user_bucket"]
+    )
+    .expect("failed to register mcp_user_tasks_in_flight metric");
+
+    pub static ref MCP_DOWNSTREAM_UP: IntGaugeVec = register_int_gauge_vec!(
+        "mcp_downstream_up",
+        "Whether the last background health ping to a downstream succeeded",
+        &["service"]
+    )
+    .expect("failed to register mcp_downstream_up metric");
+
+    pub static ref MCP_DOWNSTREAM_REJECTIONS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "mcp_downstream_rejections_total",
+        "Downstream requests refused by the host allowlist",
+        &["service"]
+    )
+    .expect("failed to register mcp_downstream_rejections_total metric");
+
+    pub static ref MCP_EVENTS_PUBLISHED_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "mcp_events_published_total",
+        "Memory events sent to the webhook by result",
+        &["result"]
+    )
+    .expect("failed to register mcp_events_published_total metric");
+
+    pub static ref MCP_REQUESTS_BY_PRIORITY_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "mcp_requests_by_priority_total",
+        "Requests seen by the concurrency limiter by priority and admission result",
+        &["priority", "result"]
+    )
+    .expect("failed to register mcp_requests_by_priority_total metric");
+
+    pub static ref MCP_RATE_LIMIT_DECISIONS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "mcp_rate_limit_decisions_total",
+        "Rate limit decisions by resolved per-minute limit bucket and result",
+        &["limit_bucket", "result"]
+    )
+    .expect("failed to register mcp_rate_limit_decisions_total metric");
+
+    pub static ref MCP_HEDGED_REQUESTS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "mcp_hedged_requests_total",
+        "Hedged memory reads by operation and result (hedged, won or capped)",
+        &["operation", "result"]
+    )
+    .expect("failed to register mcp_hedged_requests_total metric");
+}
+
+static USER_LABEL_MODE: OnceLock<UserLabelMode> = OnceLock::new();
+
+pub fn configure_user_label(mode: UserLabelMode) {
+    if USER_LABEL_MODE.set(mode).is_err() {
+        log::warn!("Metrics user label mode already configured");
+    }
+}
+
+// Batched request metrics. With batching on, each thread records requests
+// into its own local counters, behind a mutex that only the flusher ever
+// contends for, and the totals reach the shared vectors on every flush:
+// periodically, before each scrape and at shutdown. Buffers outlive their
+// threads, so nothing recorded is lost.
+
+struct LocalRequestMetrics {
+    requests: LocalIntCounterVec,
+    durations: LocalHistogramVec,
+}
+
+static BATCHING: OnceLock<bool> = OnceLock::new();
+static LOCAL_BUFFERS: Mutex<Vec<Arc<Mutex<LocalRequestMetrics>>>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static LOCAL_REQUEST_METRICS: Arc<Mutex<LocalRequestMetrics>> = register_local_buffer();
+}
+
+fn register_local_buffer() -> Arc<Mutex<LocalRequestMetrics>> {
+    let buffer = Arc::new(Mutex::new(LocalRequestMetrics {
+        requests: MCP_REQUESTS_TOTAL.local(),
+        durations: MCP_REQUEST_DURATION_SECONDS.local(),
+    }));
+    LOCAL_BUFFERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(buffer.clone());
+    buffer
+}
+
+/// Record request metrics in per-thread buffers instead of directly.
+/// Called once at startup.
+pub fn configure_batching(enabled: bool) {
+    if BATCHING.set(enabled).is_err() {
+        log::warn!("Metrics batching already configured");
+    }
+}
+
+/// Push every thread's buffered request metrics to the shared vectors.
+pub fn flush_batched() {
+    let buffers = LOCAL_BUFFERS.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    for buffer in buffers {
+        let mut local = buffer.lock().unwrap_or_else(|e| e.into_inner());
+        local.requests.flush();
+        local.durations.flush();
+    }
 }
 
 pub fn observe_request(endpoint: &str, status: &str, duration: Duration) {
+    if BATCHING.get().copied().unwrap_or(false) {
+        LOCAL_REQUEST_METRICS.with(|buffer| {
+            let mut local = buffer.lock().unwrap_or_else(|e| e.into_inner());
+            local.requests.with_label_values(&[endpoint, status]).inc();
+            local
+                .durations
+                .with_label_values(&[endpoint])
+                .observe(duration.as_secs_f64());
+        });
+        return;
+    }
     MCP_REQUESTS_TOTAL
         .with_label_values(&[endpoint, status])
         .inc();
@@ -29,10 +212,281 @@ pub fn observe_request(endpoint: &str, status: &str, duration: Duration) {
         .observe(duration.as_secs_f64());
 }
 
+/// Record a request against the user dimension, honoring the configured
+/// `UserLabelMode`. A no-op when user labels are dropped.
+pub fn observe_user_request(endpoint: &str, user_id: &str) {
+    if let Some(bucket) = user_bucket(user_id) {
+        MCP_USER_REQUESTS_TOTAL
+            .with_label_values(&[endpoint, &bucket])
+            .inc();
+    }
+}
+
+pub fn observe_user_task_started(user_id: &str) {
+    if let Some(bucket) = user_bucket(user_id) {
+        MCP_USER_TASKS_IN_FLIGHT.with_label_values(&[&bucket]).inc();
+    }
+}
+
+pub fn observe_user_task_finished(user_id: &str) {
+    if let Some(bucket) = user_bucket(user_id) {
+        MCP_USER_TASKS_IN_FLIGHT.with_label_values(&[&bucket]).dec();
+    }
+}
+
+pub fn observe_downstream_health(service: &str, up: bool) {
+    MCP_DOWNSTREAM_UP
+        .with_label_values(&[service])
+        .set(i64::from(up));
+}
+
+pub fn observe_downstream_rejection(service: &str) {
+    MCP_DOWNSTREAM_REJECTIONS_TOTAL
+        .with_label_values(&[service])
+        .inc();
+}
+
+pub fn observe_event_publish(published: bool) {
+    let result = if published { "success" } else { "failure" };
+    MCP_EVENTS_PUBLISHED_TOTAL
+        .with_label_values(&[result])
+        .inc();
+}
+
+pub fn observe_request_priority(priority: &str, admitted: bool) {
+    let result = if admitted { "admitted" } else { "shed" };
+    MCP_REQUESTS_BY_PRIORITY_TOTAL
+        .with_label_values(&[priority, result])
+        .inc();
+}
+
+pub fn observe_rate_limit(rpm: u32, allowed: bool) {
+    // Limits are configured per tenant; bucket them to bound cardinality
+    let bucket = match rpm {
+        0 => "unlimited",
+        1..=60 => "le_60",
+        61..=600 => "le_600",
+        601..=6000 => "le_6000",
+        _ => "gt_6000",
+    };
+    let result = if allowed { "allowed" } else { "limited" };
+    MCP_RATE_LIMIT_DECISIONS_TOTAL
+        .with_label_values(&[bucket, result])
+        .inc();
+}
+
+/// Hashed label for `user_id`, or `None` when user labels are dropped.
+fn user_bucket(user_id: &str) -> Option<String> {
+    let mode = USER_LABEL_MODE.get().copied().unwrap_or(UserLabelMode::Drop);
+    match mode {
+        UserLabelMode::Drop => None,
+        UserLabelMode::Hash { buckets } => {
+            let mut hasher = DefaultHasher::new();
+            user_id.hash(&mut hasher);
+            Some(format!("b{:02}", hasher.finish() % buckets))
+        }
+    }
+}
+
+const KNOWN_ACTIONS: [&str; 4] = ["edit", "save", "run", "debug"];
+
+pub fn observe_store_sampling(action: &str, stored: bool) {
+    // Actions are client-supplied; collapse unknown ones to bound cardinality
+    let action = if KNOWN_ACTIONS.contains(&action) { action } else { "other" };
+    let decision = if stored { "stored" } else { "sampled_out" };
+    MCP_MEMORY_STORE_SAMPLING_TOTAL
+        .with_label_values(&[action, decision])
+        .inc();
+}
+
+pub fn observe_context_confidence(confidence_score: f32) {
+    MCP_CONTEXT_CONFIDENCE.observe(f64::from(confidence_score));
+}
+
+/// A plain histogram that `reset_metrics` can zero. Prometheus can reset
+/// metric vectors but not a bare `Histogram`, so the registered collector
+/// reports whichever histogram is current and a reset swaps in a fresh one
+/// built from the same options.
+#[derive(Clone)]
+pub struct ResettableHistogram {
+    opts: HistogramOpts,
+    descs: Arc<Vec<Desc>>,
+    current: Arc<ArcSwap<Histogram>>,
+}
+
+impl ResettableHistogram {
+    pub fn new(opts: HistogramOpts) -> prometheus::Result<Self> {
+        let histogram = Histogram::with_opts(opts.clone())?;
+        let descs = histogram.desc().into_iter().cloned().collect();
+        Ok(Self {
+            opts,
+            descs: Arc::new(descs),
+            current: Arc::new(ArcSwap::from_pointee(histogram)),
+        })
+    }
+
+    pub fn observe(&self, value: f64) {
+        self.current.load().observe(value);
+    }
+
+    pub fn reset(&self) {
+        // The options were validated by `new`, so rebuilding cannot fail
+        if let Ok(histogram) = Histogram::with_opts(self.opts.clone()) {
+            self.current.store(Arc::new(histogram));
+        }
+    }
+}
+
+impl Collector for ResettableHistogram {
+    fn desc(&self) -> Vec<&Desc> {
+        self.descs.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.current.load().collect()
+    }
+}
+
+/// Zero every MCP metric. Only reachable through the gated admin endpoint,
+/// for resetting counters between load-test runs.
+pub fn reset_metrics() {
+    // Buffered counts predate the reset, so they are flushed away with it
+    flush_batched();
+    MCP_REQUESTS_TOTAL.reset();
+    MCP_REQUEST_DURATION_SECONDS.reset();
+    MCP_CONTEXT_CONFIDENCE.reset();
+    MCP_USER_REQUESTS_TOTAL.reset();
+    MCP_MEMORY_STORE_SAMPLING_TOTAL.reset();
+    MCP_MEMORIES_EXPORTED_TOTAL.reset();
+    MCP_MEMORY_DEDUP_HITS_TOTAL.reset();
+    MCP_TASK_DEDUP_HITS_TOTAL.reset();
+    MCP_MEMORY_QUOTA_REJECTIONS_TOTAL.reset();
+    MCP_CONTEXT_FILTERED_TOTAL.reset();
+    MCP_DOWNSTREAM_REJECTIONS_TOTAL.reset();
+    MCP_EVENTS_PUBLISHED_TOTAL.reset();
+    MCP_REQUESTS_BY_PRIORITY_TOTAL.reset();
+    MCP_RATE_LIMIT_DECISIONS_TOTAL.reset();
+    MCP_HEDGED_REQUESTS_TOTAL.reset();
+}
+
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+pub const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
 pub fn gather_metrics() -> Result<Vec<u8>, prometheus::Error> {
+    flush_batched();
     let encoder = TextEncoder::new();
     let metric_families = prometheus::gather();
     let mut buffer = Vec::new();
     encoder.encode(&metric_families, &mut buffer)?;
     Ok(buffer)
 }
+
+/// Metrics in the OpenMetrics text format. The prometheus crate has no
+/// OpenMetrics encoder, so the Prometheus text output is adapted: counter
+/// families drop the `_total` suffix in their metadata (samples keep it),
+/// `untyped` becomes `unknown`, and the exposition ends with `# EOF`.
+pub fn gather_openmetrics() -> Result<Vec<u8>, prometheus::Error> {
+    flush_batched();
+    let metric_families = prometheus::gather();
+    let counters: Vec<&str> = metric_families
+        .iter()
+        .filter(|family| family.get_field_type() == MetricType::COUNTER)
+        .map(|family| family.get_name())
+        .collect();
+
+    let mut text = Vec::new();
+    TextEncoder::new().encode(&metric_families, &mut text)?;
+    let text = String::from_utf8(text).map_err(|e| prometheus::Error::Msg(e.to_string()))?;
+
+    let mut output = String::with_capacity(text.len() + 8);
+    for line in text.lines() {
+        output.push_str(&openmetrics_line(line, &counters));
+        output.push('\n');
+    }
+    output.push_str("# EOF\n");
+    Ok(output.into_bytes())
+}
+
+fn openmetrics_line(line: &str, counters: &[&str]) -> String {
+    for prefix in ["# HELP ", "# TYPE "] {
+        if let Some(rest) = line.strip_prefix(prefix) {
+            let (name, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+            let name = if counters.contains(&name) {
+                name.strip_suffix("_total").unwrap_or(name)
+            } else {
+                name
+            };
+            let tail = if prefix == "# TYPE " && tail == "untyped" { "unknown" } else { tail };
+            return format!("{}{} {}", prefix, name, tail);
+        }
+    }
+    line.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_count(histogram: &ResettableHistogram) -> u64 {
+        histogram.collect()[0].get_metric()[0].get_histogram().get_sample_count()
+    }
+
+    #[test]
+    fn resettable_histogram_resets_without_labels() {
+        let histogram = ResettableHistogram::new(
+            HistogramOpts::new("test_confidence", "Test confidence").buckets(vec![0.5, 1.0]),
+        )
+        .unwrap();
+        histogram.observe(0.7);
+        histogram.observe(0.2);
+        assert_eq!(sample_count(&histogram), 2);
+
+        histogram.reset();
+        assert_eq!(sample_count(&histogram), 0);
+        assert!(histogram.collect()[0].get_metric()[0].get_label().is_empty());
+
+        histogram.observe(0.9);
+        assert_eq!(sample_count(&histogram), 1);
+    }
+
+    #[test]
+    fn openmetrics_metadata_drops_the_counter_total_suffix() {
+        let counters = ["mcp_requests_total"];
+        assert_eq!(
+            openmetrics_line("# TYPE mcp_requests_total counter", &counters),
+            "# TYPE mcp_requests counter"
+        );
+        assert_eq!(
+            openmetrics_line("# HELP mcp_requests_total Total MCP requests handled", &counters),
+            "# HELP mcp_requests Total MCP requests handled"
+        );
+        // Samples keep the suffix
+        assert_eq!(
+            openmetrics_line("mcp_requests_total{endpoint=\"/x\"} 1", &counters),
+            "mcp_requests_total{endpoint=\"/x\"} 1"
+        );
+    }
+
+    #[test]
+    fn openmetrics_leaves_other_families_alone_except_untyped() {
+        assert_eq!(
+            openmetrics_line("# TYPE mcp_active_total gauge", &[]),
+            "# TYPE mcp_active_total gauge"
+        );
+        assert_eq!(openmetrics_line("# TYPE legacy untyped", &[]), "# TYPE legacy unknown");
+    }
+
+    #[test]
+    fn both_formats_render_a_registered_counter() {
+        MCP_REQUESTS_TOTAL.with_label_values(&["/metrics-test", "success"]).inc();
+
+        let prometheus = String::from_utf8(gather_metrics().unwrap()).unwrap();
+        assert!(prometheus.contains("# TYPE mcp_requests_total counter"));
+        assert!(!prometheus.contains("# EOF"));
+
+        let openmetrics = String::from_utf8(gather_openmetrics().unwrap()).unwrap();
+        assert!(openmetrics.contains("# TYPE mcp_requests counter"));
+        assert!(openmetrics.contains("mcp_requests_total{endpoint=\"/metrics-test\",status=\"success\"}"));
+        assert!(openmetrics.ends_with("# EOF\n"));
+    }
+}
@@ -1,9 +1,20 @@
 // Simple authentication middleware for MCP server
 // Extracts user_id from X-User-Id header or Authorization token
 // This is an internal service, so we trust the gateway for auth validation
+// X-User-Id is only honored when the immediate peer is in TRUSTED_PROXIES
 
-use actix_web::{HttpMessage};
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error, HttpMessage, HttpResponse,
+};
+use jsonwebtoken::{decode, errors::ErrorKind, Algorithm, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::errors::McpError;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -12,17 +23,68 @@ pub struct Claims {
     pub iat: usize,   // issued at
 }
 
+// Extract the raw Bearer token from the Authorization header
+pub fn bearer_token(req: &actix_web::HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+// Decode and validate an HS256 token against JWT_SECRET.
+// `exp` and `iat` are checked with `leeway_secs` of tolerance for clock skew
+// between the gateway and this server.
+// Errors carry a short human-readable reason.
+pub fn validate_token(token: &str, secret: &str, leeway_secs: u64) -> Result<Claims, String> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = leeway_secs;
+    let claims = decode::<Claims>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation)
+        .map(|data| data.claims)
+        .map_err(|err| match err.kind() {
+            ErrorKind::ExpiredSignature => "token expired".to_string(),
+            ErrorKind::ImmatureSignature => "token not yet valid".to_string(),
+            ErrorKind::InvalidSignature => "invalid signature".to_string(),
+            _ => format!("malformed token: {}", err),
+        })?;
+
+    // jsonwebtoken doesn't check iat, so a token from the future is caught here
+    let now = jsonwebtoken::get_current_timestamp();
+    if claims.iat as u64 > now.saturating_add(leeway_secs) {
+        return Err("token not yet valid".to_string());
+    }
+
+    Ok(claims)
+}
+
+/// The caller's user id as resolved for this request, kept in the request
+/// extensions so middleware and handlers share one lookup (and its warnings).
+#[derive(Debug, Clone)]
+pub struct ResolvedUserId(pub Option<String>);
+
 // Helper function to extract user_id from request
-// This can be called in route handlers
+// This can be called in middleware and route handlers; only the first call
+// per request resolves it, later ones read the stored result
 pub fn extract_user_id(req: &actix_web::HttpRequest) -> Option<String> {
-    // First try to get from extensions (if set by upstream middleware)
-    if let Some(user_id) = req.extensions().get::<String>() {
-        return Some(user_id.clone());
+    if let Some(ResolvedUserId(user_id)) = req.extensions().get::<ResolvedUserId>() {
+        return user_id.clone();
     }
-    
+
+    let user_id = resolve_user_id(req);
+    req.extensions_mut().insert(ResolvedUserId(user_id.clone()));
+    user_id
+}
+
+fn resolve_user_id(req: &actix_web::HttpRequest) -> Option<String> {
     // Try X-User-Id header
     if let Some(header_value) = req.headers().get("X-User-Id") {
-        if let Ok(user_id) = header_value.to_str() {
+        if !peer_is_trusted(req) {
+            log::warn!(
+                "Ignoring X-User-Id from untrusted peer {:?}",
+                req.peer_addr()
+            );
+        } else if let Ok(user_id) = header_value.to_str() {
             return Some(user_id.to_string());
         }
     }
@@ -41,3 +103,230 @@ pub fn extract_user_id(req: &actix_web::HttpRequest) -> Option<String> {
     
     None
 }
+
+// Tenant or plan tier set by the gateway in X-Tenant-Id. Like X-User-Id, it
+// is only honored from trusted peers.
+pub fn extract_tenant(req: &actix_web::HttpRequest) -> Option<String> {
+    let value = req.headers().get("X-Tenant-Id")?.to_str().ok()?.trim();
+    if value.is_empty() || !peer_is_trusted(req) {
+        return None;
+    }
+    Some(value.to_string())
+}
+
+
+// Per-request downstream timeout from X-Timeout-Ms, clamped to
+// MAX_REQUEST_TIMEOUT_MS. Returns None when the header is absent so the
+// client's configured default applies.
+pub fn request_timeout(
+    req: &actix_web::HttpRequest,
+    config: &Config,
+) -> Result<Option<Duration>, McpError> {
+    let header_value = match req.headers().get("X-Timeout-Ms") {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    let millis: u64 = header_value
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or_else(|| {
+            McpError::InvalidRequest("X-Timeout-Ms must be a positive integer".to_string())
+        })?;
+    if millis == 0 {
+        return Err(McpError::InvalidRequest(
+            "X-Timeout-Ms must be greater than zero".to_string(),
+        ));
+    }
+
+    Ok(Some(Duration::from_millis(millis.min(config.max_request_timeout_ms))))
+}
+
+// Check the immediate peer against TRUSTED_PROXIES
+pub(crate) fn peer_is_trusted(req: &actix_web::HttpRequest) -> bool {
+    let config = match req.app_data::<web::Data<Config>>() {
+        Some(config) => config,
+        None => return true,
+    };
+    match req.peer_addr() {
+        Some(peer) => config.is_trusted_proxy(peer.ip()),
+        None => config.trusted_proxies.is_empty(),
+    }
+}
+
+// Guard for admin endpoints: requires X-Admin-Token to match ADMIN_TOKEN.
+// Admin endpoints are unusable when no token is configured.
+pub fn require_admin(req: &actix_web::HttpRequest, config: &Config) -> Result<(), McpError> {
+    let expected = config
+        .admin_token
+        .as_deref()
+        .ok_or_else(|| McpError::Unauthorized("Admin endpoints are disabled".to_string()))?;
+
+    let provided = req
+        .headers()
+        .get("X-Admin-Token")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+
+    if provided.len() != expected.len()
+        || provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            != 0
+    {
+        return Err(McpError::Unauthorized("Invalid admin token".to_string()));
+    }
+
+    Ok(())
+}
+
+// Streaming endpoints legitimately run for as long as the transfer takes
+const DEADLINE_EXEMPT_PATHS: [&str; 2] = ["/mcp/memory/export", "/mcp/memory/import"];
+
+// Overall wall-clock limit per request (REQUEST_DEADLINE_MS). A handler still
+// running at the deadline is dropped, which cancels its downstream calls, and
+// the client gets 504.
+pub async fn request_deadline<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let deadline_ms = req
+        .app_data::<web::Data<Config>>()
+        .map(|config| config.request_deadline_ms)
+        .unwrap_or(0);
+    if deadline_ms == 0 || DEADLINE_EXEMPT_PATHS.contains(&req.path()) {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    let request = req.request().clone();
+    match tokio::time::timeout(Duration::from_millis(deadline_ms), next.call(req)).await {
+        Ok(res) => res.map(ServiceResponse::map_into_left_body),
+        Err(_) => {
+            log::warn!(
+                "{} {} exceeded the {}ms request deadline",
+                request.method(),
+                request.path(),
+                deadline_ms
+            );
+            let response = HttpResponse::from_error(McpError::GatewayTimeout(format!(
+                "request did not complete within {}ms",
+                deadline_ms
+            )));
+            Ok(ServiceResponse::new(request, response).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    const SECRET: &str = "test-secret";
+    const LEEWAY: u64 = 60;
+
+    /// Token whose `exp` and `iat` are offset from now by the given seconds.
+    fn token(exp_offset: i64, iat_offset: i64) -> String {
+        let now = jsonwebtoken::get_current_timestamp() as i64;
+        let claims = Claims {
+            sub: "alice".to_string(),
+            exp: (now + exp_offset) as usize,
+            iat: (now + iat_offset) as usize,
+        };
+        encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(SECRET.as_bytes()))
+            .unwrap()
+    }
+
+    #[test]
+    fn current_token_is_accepted() {
+        let claims = validate_token(&token(3600, 0), SECRET, LEEWAY).unwrap();
+        assert_eq!(claims.sub, "alice");
+    }
+
+    #[test]
+    fn expiry_is_tolerated_within_the_leeway() {
+        assert!(validate_token(&token(-30, -3600), SECRET, LEEWAY).is_ok());
+        assert_eq!(
+            validate_token(&token(-120, -3600), SECRET, LEEWAY).unwrap_err(),
+            "token expired"
+        );
+        assert_eq!(
+            validate_token(&token(-30, -3600), SECRET, 0).unwrap_err(),
+            "token expired"
+        );
+    }
+
+    #[test]
+    fn issued_in_the_future_is_tolerated_only_within_the_leeway() {
+        assert!(validate_token(&token(3600, 30), SECRET, LEEWAY).is_ok());
+        assert_eq!(
+            validate_token(&token(3600, 120), SECRET, LEEWAY).unwrap_err(),
+            "token not yet valid"
+        );
+    }
+
+    #[test]
+    fn wrong_secret_is_rejected() {
+        assert_eq!(
+            validate_token(&token(3600, 0), "other-secret", LEEWAY).unwrap_err(),
+            "invalid signature"
+        );
+    }
+
+    async fn slow_handler() -> HttpResponse {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        HttpResponse::Ok().finish()
+    }
+
+    async fn call_with_deadline(path: &str, deadline_ms: u64) -> actix_web::http::StatusCode {
+        use actix_web::{middleware::from_fn, test, App};
+
+        let mut config = Config::for_tests();
+        config.request_deadline_ms = deadline_ms;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .wrap(from_fn(request_deadline))
+                .route("/mcp/slow", web::get().to(slow_handler))
+                .route("/mcp/memory/export", web::get().to(slow_handler)),
+        )
+        .await;
+        let req = test::TestRequest::get().uri(path).to_request();
+        test::call_service(&app, req).await.status()
+    }
+
+    #[actix_web::test]
+    async fn slow_handler_past_the_deadline_gets_504() {
+        let status = call_with_deadline("/mcp/slow", 50).await;
+        assert_eq!(status, actix_web::http::StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[actix_web::test]
+    async fn no_deadline_or_exempt_path_lets_the_handler_finish() {
+        assert!(call_with_deadline("/mcp/slow", 0).await.is_success());
+        assert!(call_with_deadline("/mcp/memory/export", 50).await.is_success());
+    }
+
+    #[test]
+    fn user_id_is_resolved_once_per_request() {
+        let req = actix_web::test::TestRequest::default()
+            .insert_header(("X-User-Id", "alice"))
+            .to_http_request();
+        assert_eq!(extract_user_id(&req).as_deref(), Some("alice"));
+        assert!(matches!(
+            req.extensions().get::<ResolvedUserId>(),
+            Some(ResolvedUserId(Some(user_id))) if user_id == "alice"
+        ));
+    }
+
+    #[test]
+    fn stored_resolution_is_reused() {
+        let req = actix_web::test::TestRequest::default()
+            .insert_header(("X-User-Id", "alice"))
+            .to_http_request();
+        req.extensions_mut().insert(ResolvedUserId(None));
+        assert_eq!(extract_user_id(&req), None);
+    }
+}
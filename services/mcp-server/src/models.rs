@@ -1,6 +1,10 @@
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::circuit::CircuitState;
+
 // Request/Response models for MCP endpoints
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -9,21 +13,141 @@ pub struct ContextFetchRequest {
     pub file_content: Option<String>,
     pub language: Option<String>,
     pub limit: Option<usize>,
+    pub boost_tags: Option<Vec<String>>,
+    /// Token from a previous response; the other search fields must repeat
+    /// the first request
+    pub page_token: Option<String>,
+    /// Include a relevance explanation with each memory
+    pub explain: Option<bool>,
+    /// Drop memories scored below this; overrides MIN_CONTEXT_CONFIDENCE.
+    /// Only read on the first page: page tokens carry the cutoff
+    pub min_confidence: Option<f32>,
+    /// Also return the memories grouped by tier
+    pub group_by_tier: Option<bool>,
+    /// `user` (default) or `team`
+    pub scope: Option<String>,
+    /// Team to search when `scope` is `team`
+    pub team_id: Option<String>,
 }
 
+/// Version 1 context fetch schema: the file only, without ranking or
+/// paging options.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContextFetchRequestV1 {
+    pub file_path: String,
+    pub file_content: Option<String>,
+    pub language: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl From<ContextFetchRequestV1> for ContextFetchRequest {
+    fn from(v1: ContextFetchRequestV1) -> Self {
+        Self {
+            file_path: v1.file_path,
+            file_content: v1.file_content,
+            language: v1.language,
+            limit: v1.limit,
+            boost_tags: None,
+            page_token: None,
+            explain: None,
+            min_confidence: None,
+            group_by_tier: None,
+            scope: None,
+            team_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextFetchResponse {
     pub memories: Vec<MemoryItem>,
     pub context_summary: String,
+    pub partial_results: bool,
+    pub next_page_token: Option<String>,
+    /// Every returned memory is older than the staleness threshold
+    pub stale_context: bool,
+    /// Age in seconds of the newest returned memory with a valid timestamp
+    pub newest_memory_age_secs: Option<i64>,
+    /// Served from the local fallback cache during a memory service outage
+    pub from_cache: bool,
+    /// The cached response may be out of date
+    pub stale: bool,
+    /// Non-fatal problems with the request, e.g. an unrecognized language
+    pub warnings: Vec<String>,
+    /// Memories on this page dropped for falling below the confidence cutoff
+    pub filtered_count: usize,
+    /// `memories` keyed by tier, when the client asked for grouping
+    pub grouped: Option<BTreeMap<String, Vec<MemoryItem>>>,
+    /// Language inferred from the file extension when the request had none
+    pub inferred_language: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MemoryItem {
     pub id: String,
+    /// Owner, when the memory service reports it
+    pub user_id: Option<String>,
     pub content: String,
     pub tier: String,
+    /// Confidence normalized to 0-1; 0 when the memory service gave none
     pub confidence_score: f32,
+    /// Confidence as the memory service reported it; `None` when absent
+    pub raw_confidence: Option<f64>,
     pub created_at: String,
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    /// Why this memory was surfaced; only set when the client asks to explain
+    pub explanation: Option<MemoryExplanation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryExplanation {
+    /// False when the breakdown came from the memory service
+    pub synthesized: bool,
+    /// Scoring factors, e.g. term matches and recency
+    pub factors: serde_json::Value,
+}
+
+impl MemoryItem {
+    /// `created_at` as a UTC timestamp. Memory service timestamps are
+    /// ISO 8601 with or without an offset; anything else yields `None`.
+    pub fn created_at_utc(&self) -> Option<DateTime<Utc>> {
+        parse_timestamp(&self.created_at)
+    }
+}
+
+/// Parse the timestamp formats memory services are seen to send: RFC 3339,
+/// ISO 8601 without an offset (`T` or space separated, taken as UTC),
+/// RFC 2822, and Unix epochs in seconds or milliseconds.
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                .map(|dt| dt.and_utc())
+        })
+        .or_else(|| {
+            DateTime::parse_from_rfc2822(value)
+                .map(|dt| dt.with_timezone(&Utc))
+                .ok()
+        })
+        .or_else(|| {
+            let epoch: f64 = value.parse().ok()?;
+            // Anything past the year 5138 in seconds is taken as milliseconds
+            let millis = if epoch.abs() >= 1e11 { epoch } else { epoch * 1000.0 };
+            DateTime::from_timestamp_millis(millis as i64)
+        })
+}
+
+/// `value` as an RFC 3339 UTC timestamp, or `None` if it can't be parsed.
+pub fn normalize_timestamp(value: &str) -> Option<String> {
+    parse_timestamp(value).map(|dt| dt.to_rfc3339_opts(SecondsFormat::AutoSi, true))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,6 +157,8 @@ pub struct MemoryLogRequest {
     pub content: Option<String>,
     pub outcome: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    /// Extra tags stored alongside the action tags
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,11 +168,57 @@ pub struct MemoryLogResponse {
     pub message: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryExportQuery {
+    pub tier: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryImportQuery {
+    pub fail_fast: Option<bool>,
+}
+
+/// One NDJSON line of an import; matches the records produced by export.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryImportRecord {
+    #[serde(rename = "type")]
+    pub memory_type: String,
+    pub input_context: String,
+    pub output_response: Option<String>,
+    pub outcome: Option<String>,
+    pub tier: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryImportResponse {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub malformed: usize,
+    /// Records that could not be stored, in input order
+    pub failures: Vec<BatchItemFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchItemFailure {
+    /// Position among the well-formed records, from 0
+    pub index: usize,
+    pub status: u16,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryConsolidateResponse {
+    pub consolidated: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TaskSubmitRequest {
     pub task_description: String,
     pub file_context: Option<String>,
     pub session_id: Option<Uuid>,
+    pub template: Option<String>,
+    pub language: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -54,6 +226,21 @@ pub struct TaskSubmitResponse {
     pub session_id: String,
     pub response: String,
     pub tokens_used: Option<i32>,
+    pub fallback: bool,
+    pub memories: Option<Vec<MemoryItem>>,
+    /// Size of the prompt sent upstream; `None` for fallback responses
+    pub prompt_stats: Option<PromptStats>,
+    /// Estimated from `tokens_used` and MODEL_PRICES; `None` when unpriced
+    pub estimated_cost_usd: Option<f64>,
+    /// Non-fatal problems, e.g. no price for the model used
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PromptStats {
+    /// Characters in the assembled message
+    pub characters: usize,
+    pub estimated_tokens: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -62,6 +249,86 @@ pub struct HealthResponse {
     pub version: String,
     pub memory_service: bool,
     pub intelligence_service: bool,
+    pub read_only: bool,
+    pub circuits: CircuitsStatus,
+    pub dependencies: DependenciesStatus,
+}
+
+/// Lightweight operational view: process counters plus the background
+/// health monitor's last ping.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub version: String,
+    pub uptime_secs: u64,
+    /// Requests received since startup, including this one
+    pub requests_total: u64,
+    /// Requests currently being handled, including this one
+    pub requests_in_flight: u64,
+    pub read_only: bool,
+    /// `None` until the first ping completes
+    pub memory_service: Option<bool>,
+    pub intelligence_service: Option<bool>,
+    /// Switchable endpoints that are on; see the `ENABLE_*` settings
+    pub enabled_endpoints: Vec<String>,
+}
+
+/// Readiness from the background health monitor's last ping.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub dependencies: DependenciesStatus,
+    /// When the last ping completed; `None` before the first one
+    pub checked_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DependenciesStatus {
+    pub memory_service: DependencyStatus,
+    pub intelligence_service: DependencyStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Up,
+    /// Down, but the deployment runs without it
+    DownOptional,
+    /// Down and required, so the server is degraded
+    DownRequired,
+}
+
+impl DependencyStatus {
+    pub fn new(up: bool, required: bool) -> Self {
+        match (up, required) {
+            (true, _) => DependencyStatus::Up,
+            (false, false) => DependencyStatus::DownOptional,
+            (false, true) => DependencyStatus::DownRequired,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CircuitsStatus {
+    pub memory_service: CircuitState,
+    pub intelligence_service: CircuitState,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadOnlyRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadOnlyResponse {
+    pub read_only: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenValidationResponse {
+    pub valid: bool,
+    pub user_id: Option<String>,
+    pub expires_at: Option<String>,
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -77,6 +344,19 @@ pub struct MemorySearchRequest {
     pub query: String,
     pub limit: Option<usize>,
     pub tier: Option<String>,
+    pub boost_tags: Option<Vec<String>>,
+    pub explain: Option<bool>,
+    /// Search these users' memories instead of the caller's; only ever
+    /// set from the configured team membership
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_ids: Option<Vec<String>>,
+}
+
+/// Merged search results; `partial` is set when some regions failed.
+#[derive(Debug)]
+pub struct MemorySearchResults {
+    pub memories: Vec<MemoryItem>,
+    pub partial: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -97,8 +377,10 @@ pub struct ChatMessageRequest {
     pub use_memory: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessageResponse {
+    /// Empty when the intelligence service leaves it out
+    #[serde(default)]
     pub session_id: String,
     pub response: String,
     pub tokens_used: Option<i32>,
@@ -0,0 +1,330 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::errors::McpError;
+use crate::models::{parse_timestamp, MemoryItem};
+
+// Pagination for list and search endpoints.
+// `PaginationParams` holds the shared limit/offset policy. `SearchCursor` is
+// the stateless, tamper-proof cursor for context search: it carries a hash of
+// the query plus a snapshot time. Tokens are only signed, not encrypted, so
+// the query itself (which may include file content) is never put in one;
+// later pages rebuild it from the request, and the hash makes sure it is the
+// same query. Memories stored after the first page are ignored. Offsets count
+// only memories that meet the cursor's confidence cutoff, so low-confidence
+// memories never leave a page short.
+
+/// Largest result window the memory service will return for one search.
+pub const MAX_SEARCH_WINDOW: usize = 100;
+
+const TOKEN_TTL_SECS: i64 = 3600;
+
+/// Validated limit and offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaginationParams {
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl PaginationParams {
+    pub const DEFAULT_LIMIT: usize = 5;
+    pub const MAX_LIMIT: usize = MAX_SEARCH_WINDOW;
+
+    /// A missing limit takes the default and one above the maximum is
+    /// clamped; a zero limit is rejected. A missing offset starts at zero.
+    pub fn new(limit: Option<usize>, offset: Option<usize>) -> Result<Self, McpError> {
+        let limit = match limit {
+            None => Self::DEFAULT_LIMIT,
+            Some(0) => {
+                return Err(McpError::InvalidRequest(
+                    "limit must be at least 1".to_string(),
+                ))
+            }
+            Some(limit) => limit.min(Self::MAX_LIMIT),
+        };
+        Ok(Self {
+            limit,
+            offset: offset.unwrap_or(0),
+        })
+    }
+}
+
+/// One page of search results, with whether more follow and how many
+/// memories were dropped for low confidence.
+#[derive(Debug, Default)]
+pub struct SearchPage {
+    pub memories: Vec<MemoryItem>,
+    pub has_more: bool,
+    pub filtered_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchCursor {
+    /// User the cursor was issued to
+    pub sub: String,
+    /// SHA-256 of the query the first page searched for
+    pub query_hash: String,
+    pub limit: usize,
+    pub boost_tags: Option<Vec<String>>,
+    /// Team searched instead of the user's own memories
+    #[serde(default)]
+    pub team_id: Option<String>,
+    /// Confidence cutoff of the first page, kept so offsets stay stable
+    #[serde(default)]
+    pub min_confidence: f32,
+    pub offset: usize,
+    /// RFC 3339 time of the first page; newer memories are skipped
+    pub snapshot: String,
+    exp: i64,
+}
+
+impl SearchCursor {
+    pub fn first_page(
+        user_id: &str,
+        query: &str,
+        page: PaginationParams,
+        boost_tags: Option<Vec<String>>,
+        team_id: Option<String>,
+        min_confidence: f32,
+    ) -> Self {
+        Self {
+            sub: user_id.to_string(),
+            query_hash: query_hash(query),
+            limit: page.limit,
+            boost_tags,
+            team_id,
+            min_confidence,
+            offset: page.offset,
+            snapshot: Utc::now().to_rfc3339(),
+            exp: 0,
+        }
+    }
+
+    /// Whether the cursor continues a search for `query`.
+    pub fn is_for_query(&self, query: &str) -> bool {
+        self.query_hash == query_hash(query)
+    }
+
+    /// How many results to request so this page (plus one lookahead item)
+    /// is covered.
+    pub fn window(&self) -> usize {
+        (self.offset + self.limit + 1).min(MAX_SEARCH_WINDOW)
+    }
+
+    pub fn next_page(&self) -> Self {
+        Self {
+            offset: self.offset + self.limit,
+            ..self.clone()
+        }
+    }
+
+    /// This cursor's page of `ranked`. Memories below the confidence cutoff
+    /// are skipped without taking a place, and counted against the page
+    /// they would have appeared on. Unscored memories can't be judged and
+    /// are kept.
+    pub fn page(&self, ranked: Vec<MemoryItem>) -> SearchPage {
+        let end = self.offset + self.limit;
+        let mut page = SearchPage::default();
+        let mut kept = 0;
+        for memory in ranked {
+            let on_page = kept >= self.offset && kept < end;
+            if memory.raw_confidence.is_some() && memory.confidence_score < self.min_confidence {
+                if on_page {
+                    page.filtered_count += 1;
+                }
+                continue;
+            }
+            if on_page {
+                page.memories.push(memory);
+            }
+            kept += 1;
+        }
+        page.has_more = kept > end;
+        page
+    }
+
+    /// Whether `memory` existed when the first page was served.
+    /// Unparseable timestamps are kept.
+    pub fn includes(&self, memory: &MemoryItem) -> bool {
+        match (parse_timestamp(&self.snapshot), memory.created_at_utc()) {
+            (Some(snapshot), Some(created)) => created <= snapshot,
+            _ => true,
+        }
+    }
+
+    pub fn encode(&self, secret: &str) -> Result<String, McpError> {
+        let claims = Self {
+            exp: Utc::now().timestamp() + TOKEN_TTL_SECS,
+            ..self.clone()
+        };
+        encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .map_err(|e| McpError::InternalError(format!("Failed to sign page token: {}", e)))
+    }
+
+    /// Verify a client-supplied token and check it belongs to `user_id`.
+    pub fn decode(token: &str, secret: &str, user_id: &str) -> Result<Self, McpError> {
+        let cursor = decode::<Self>(
+            token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map(|data| data.claims)
+        .map_err(|e| McpError::InvalidRequest(format!("Invalid page token: {}", e)))?;
+
+        if cursor.sub != user_id {
+            return Err(McpError::InvalidRequest(
+                "Invalid page token: issued to another user".to_string(),
+            ));
+        }
+        let in_range = PaginationParams::new(Some(cursor.limit), Some(cursor.offset))
+            .map(|page| page.limit == cursor.limit && page.offset < MAX_SEARCH_WINDOW)
+            .unwrap_or(false);
+        if !in_range {
+            return Err(McpError::InvalidRequest(
+                "Invalid page token: position out of range".to_string(),
+            ));
+        }
+        Ok(cursor)
+    }
+}
+
+fn query_hash(query: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(query.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+
+    #[test]
+    fn limit_defaults_and_clamps() {
+        let page = PaginationParams::new(None, None).unwrap();
+        assert_eq!(page, PaginationParams { limit: PaginationParams::DEFAULT_LIMIT, offset: 0 });
+
+        let page = PaginationParams::new(Some(10_000), Some(20)).unwrap();
+        assert_eq!(page, PaginationParams { limit: PaginationParams::MAX_LIMIT, offset: 20 });
+
+        let page = PaginationParams::new(Some(1), None).unwrap();
+        assert_eq!(page.limit, 1);
+    }
+
+    #[test]
+    fn zero_limit_is_rejected() {
+        assert!(matches!(
+            PaginationParams::new(Some(0), None),
+            Err(McpError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn page_tokens_outside_the_search_window_are_rejected() {
+        let mut cursor = first_page("query");
+        cursor.offset = MAX_SEARCH_WINDOW;
+        let token = cursor.encode(SECRET).unwrap();
+        assert!(SearchCursor::decode(&token, SECRET, "alice").is_err());
+
+        cursor.offset = 0;
+        cursor.limit = 0;
+        let token = cursor.encode(SECRET).unwrap();
+        assert!(SearchCursor::decode(&token, SECRET, "alice").is_err());
+    }
+
+    #[test]
+    fn window_covers_the_page_plus_one_up_to_the_maximum() {
+        let cursor = first_page("query");
+        assert_eq!(cursor.window(), 6);
+        assert_eq!(cursor.next_page().window(), 11);
+
+        let mut deep = first_page("query");
+        deep.offset = 98;
+        assert_eq!(deep.window(), MAX_SEARCH_WINDOW);
+    }
+
+    fn first_page(query: &str) -> SearchCursor {
+        let page = PaginationParams::new(Some(5), None).unwrap();
+        SearchCursor::first_page("alice", query, page, None, None, 0.0)
+    }
+
+    fn scored(id: usize, confidence: Option<f32>) -> MemoryItem {
+        MemoryItem {
+            id: format!("m{}", id),
+            user_id: None,
+            content: String::new(),
+            tier: "ltm".to_string(),
+            confidence_score: confidence.unwrap_or(0.0),
+            raw_confidence: confidence.map(f64::from),
+            created_at: String::new(),
+            tags: Vec::new(),
+            source: None,
+            metadata: None,
+            explanation: None,
+        }
+    }
+
+    fn ids(page: &SearchPage) -> Vec<&str> {
+        page.memories.iter().map(|memory| memory.id.as_str()).collect()
+    }
+
+    #[test]
+    fn low_confidence_memories_do_not_shorten_pages() {
+        let mut cursor = first_page("query");
+        cursor.limit = 2;
+        cursor.min_confidence = 0.5;
+        // m1 and m3 are below the cutoff; m4 is unscored and kept
+        let ranked = || {
+            vec![
+                scored(0, Some(0.9)),
+                scored(1, Some(0.1)),
+                scored(2, Some(0.8)),
+                scored(3, Some(0.2)),
+                scored(4, None),
+                scored(5, Some(0.7)),
+            ]
+        };
+
+        let first = cursor.page(ranked());
+        assert_eq!(ids(&first), ["m0", "m2"]);
+        assert_eq!(first.filtered_count, 1);
+        assert!(first.has_more);
+
+        let second = cursor.next_page().page(ranked());
+        assert_eq!(ids(&second), ["m4", "m5"]);
+        assert_eq!(second.filtered_count, 1);
+        assert!(!second.has_more);
+    }
+
+    #[test]
+    fn token_does_not_carry_the_query() {
+        let query = "File: secret.rs\nfn leaked_function_name() {}";
+        let token = first_page(query).encode(SECRET).unwrap();
+        let payload = token.split('.').nth(1).unwrap();
+        let claims = String::from_utf8(URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+        assert!(!claims.contains("leaked_function_name"));
+        assert!(!claims.contains("secret.rs"));
+    }
+
+    #[test]
+    fn next_page_is_bound_to_the_same_query() {
+        let token = first_page("query one").next_page().encode(SECRET).unwrap();
+        let cursor = SearchCursor::decode(&token, SECRET, "alice").unwrap();
+        assert_eq!(cursor.offset, 5);
+        assert!(cursor.is_for_query("query one"));
+        assert!(!cursor.is_for_query("query two"));
+    }
+
+    #[test]
+    fn foreign_and_tampered_tokens_are_rejected() {
+        let token = first_page("query").encode(SECRET).unwrap();
+        assert!(SearchCursor::decode(&token, SECRET, "bob").is_err());
+        assert!(SearchCursor::decode(&token, "other-secret", "alice").is_err());
+    }
+}
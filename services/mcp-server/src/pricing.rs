@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+// Estimated task cost in USD from a per-model price table. Prices are per
+// million tokens, split into input and output rates. The Intelligence Core
+// doesn't report which model answered, so tasks are priced at the model it is
+// configured with (`INTELLIGENCE_MODEL`, `default` unless set).
+
+pub const DEFAULT_MODEL: &str = "default";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    /// Parse `model=input:output` entries, e.g. `gpt-4o=2.5:10,default=0.5:1.5`.
+    pub fn parse(spec: &[String]) -> Self {
+        let prices = spec
+            .iter()
+            .map(|entry| {
+                let (model, rates) = entry
+                    .split_once('=')
+                    .unwrap_or_else(|| panic!("MODEL_PRICES entry '{}' must be model=input:output", entry));
+                let (input, output) = rates
+                    .split_once(':')
+                    .unwrap_or_else(|| panic!("MODEL_PRICES rates '{}' must be input:output", rates));
+                let rate = |value: &str| -> f64 {
+                    value
+                        .trim()
+                        .parse()
+                        .unwrap_or_else(|_| panic!("MODEL_PRICES rate '{}' is not a number", value))
+                };
+                let price = ModelPrice {
+                    input_per_million: rate(input),
+                    output_per_million: rate(output),
+                };
+                (model.trim().to_string(), price)
+            })
+            .collect();
+        Self { prices }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.prices.is_empty()
+    }
+
+    /// Cost of a task, or `None` when `model` has no price.
+    pub fn estimate(&self, model: &str, input_tokens: u64, output_tokens: u64) -> Option<f64> {
+        let price = self.prices.get(model)?;
+        let cost = input_tokens as f64 * price.input_per_million
+            + output_tokens as f64 * price.output_per_million;
+        Some(cost / 1_000_000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_the_named_model() {
+        let table = PriceTable::parse(&[
+            "gpt-4o=2.5:10".to_string(),
+            "default=0.5:1.5".to_string(),
+        ]);
+        assert_eq!(table.estimate("gpt-4o", 1_000_000, 100_000), Some(3.5));
+        assert_eq!(table.estimate(DEFAULT_MODEL, 1_000_000, 0), Some(0.5));
+        assert_eq!(table.estimate("claude", 1, 1), None);
+    }
+}
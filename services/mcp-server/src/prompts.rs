@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs;
+
+// Server-side prompt templates for task submission.
+// Placeholders: {task}, {file_context}, {language}
+
+const DEFAULT_TEMPLATES: [(&str, &str); 3] = [
+    (
+        "explain",
+        "Explain the following {language} code and what it does.\n\nFile Context:\n{file_context}\n\nTask: {task}",
+    ),
+    (
+        "refactor",
+        "Refactor the following {language} code for readability and maintainability without changing behavior.\n\nFile Context:\n{file_context}\n\nTask: {task}",
+    ),
+    (
+        "fix-bug",
+        "Find and fix the bug in the following {language} code. Explain the root cause.\n\nFile Context:\n{file_context}\n\nTask: {task}",
+    ),
+];
+
+/// Built-in templates, overridden or extended by a JSON object
+/// (`{"name": "template"}`) read from `path` when given.
+pub fn load_templates(path: Option<&str>) -> HashMap<String, String> {
+    let mut templates: HashMap<String, String> = DEFAULT_TEMPLATES
+        .iter()
+        .map(|(name, template)| (name.to_string(), template.to_string()))
+        .collect();
+
+    if let Some(path) = path {
+        let contents = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("PROMPT_TEMPLATES_FILE {} could not be read: {}", path, e));
+        let overrides: HashMap<String, String> = serde_json::from_str(&contents)
+            .unwrap_or_else(|e| panic!("PROMPT_TEMPLATES_FILE {} is not valid JSON: {}", path, e));
+        templates.extend(overrides);
+    }
+
+    templates
+}
+
+/// Fill the placeholders in a single pass over `template`, so placeholder
+/// text inside the substituted values is left as the user wrote it.
+/// Unknown `{...}` sequences are copied through.
+pub fn render_template(
+    template: &str,
+    task: &str,
+    file_context: Option<&str>,
+    language: Option<&str>,
+) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        let placeholder = &rest[open..];
+        let value = [
+            ("{file_context}", file_context.unwrap_or("")),
+            ("{language}", language.unwrap_or("source")),
+            ("{task}", task),
+        ]
+        .into_iter()
+        .find(|(name, _)| placeholder.starts_with(name));
+        match value {
+            Some((name, value)) => {
+                rendered.push_str(value);
+                rest = &placeholder[name.len()..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Rough token estimate for English text and code: about four characters
+/// per token, rounded up.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_every_placeholder() {
+        let rendered = render_template(
+            "{language}: {task}\n{file_context}\n{task}",
+            "explain",
+            Some("fn main() {}"),
+            Some("rust"),
+        );
+        assert_eq!(rendered, "rust: explain\nfn main() {}\nexplain");
+    }
+
+    #[test]
+    fn placeholders_inside_user_text_are_not_substituted() {
+        let rendered = render_template(
+            "Context:\n{file_context}\nTask: {task}",
+            "print {file_context} and {language}",
+            Some("let s = \"{task}\";"),
+            None,
+        );
+        assert_eq!(
+            rendered,
+            "Context:\nlet s = \"{task}\";\nTask: print {file_context} and {language}"
+        );
+    }
+
+    #[test]
+    fn missing_values_and_unknown_braces() {
+        let rendered = render_template("{language} {file_context}{unknown} {", "t", None, None);
+        assert_eq!(rendered, "source {unknown} {");
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+
+use crate::models::ContextFetchRequest;
+
+// Strategies for turning a context fetch request into a memory search query.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryStrategy {
+    /// Only the file path
+    PathOnly,
+    /// File path plus the first and last N lines of content
+    PathPlusSnippet { lines: usize },
+    /// File path plus the entire content (original behavior)
+    Full,
+    /// File path plus identifiers extracted from the content
+    Symbols,
+}
+
+const MAX_SYMBOLS: usize = 50;
+
+const KEYWORDS: [&str; 24] = [
+    "and", "async", "await", "class", "const", "def", "else", "enum", "false", "for", "function",
+    "impl", "import", "let", "mut", "none", "null", "pub", "return", "self", "struct", "the",
+    "true", "use",
+];
+
+impl QueryStrategy {
+    pub fn parse(name: &str, snippet_lines: usize) -> Self {
+        match name {
+            "path_only" => QueryStrategy::PathOnly,
+            "path_plus_snippet" => QueryStrategy::PathPlusSnippet {
+                lines: snippet_lines,
+            },
+            "symbols" => QueryStrategy::Symbols,
+            "full" => QueryStrategy::Full,
+            other => panic!(
+                "CONTEXT_QUERY_STRATEGY must be path_only, path_plus_snippet, full or symbols, got '{}'",
+                other
+            ),
+        }
+    }
+}
+
+pub fn build_query(strategy: QueryStrategy, request: &ContextFetchRequest) -> String {
+    let content = match &request.file_content {
+        Some(content) if !content.trim().is_empty() => content,
+        _ => return request.file_path.clone(),
+    };
+
+    let detail = match strategy {
+        QueryStrategy::PathOnly => return request.file_path.clone(),
+        QueryStrategy::Full => content.clone(),
+        QueryStrategy::PathPlusSnippet { lines } => snippet(content, lines),
+        QueryStrategy::Symbols => symbols(content).join(" "),
+    };
+
+    format!("{} {}", request.file_path, detail)
+}
+
+/// First and last `lines` lines of `content`, or all of it if short enough.
+fn snippet(content: &str, lines: usize) -> String {
+    let all: Vec<&str> = content.lines().collect();
+    if all.len() <= lines * 2 {
+        return all.join("\n");
+    }
+    let mut parts = all[..lines].to_vec();
+    parts.extend_from_slice(&all[all.len() - lines..]);
+    parts.join("\n")
+}
+
+/// Distinct identifiers in order of first appearance, skipping short tokens,
+/// numbers and common keywords.
+fn symbols(content: &str) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    content
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| token.len() >= 3)
+        .filter(|token| token.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+        .filter(|token| !KEYWORDS.contains(&token.to_ascii_lowercase().as_str()))
+        .filter(|token| seen.insert(*token))
+        .take(MAX_SYMBOLS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(file_content: Option<&str>) -> ContextFetchRequest {
+        serde_json::from_value(serde_json::json!({
+            "file_path": "src/lib.rs",
+            "file_content": file_content,
+        }))
+        .unwrap()
+    }
+
+    const CONTENT: &str = "use std::fmt;\nfn parse_config() {}\nlet value = 42;\nfn render_page() {}";
+
+    #[test]
+    fn without_content_every_strategy_uses_the_path() {
+        for strategy in [
+            QueryStrategy::PathOnly,
+            QueryStrategy::PathPlusSnippet { lines: 1 },
+            QueryStrategy::Full,
+            QueryStrategy::Symbols,
+        ] {
+            assert_eq!(build_query(strategy, &request(None)), "src/lib.rs");
+            assert_eq!(build_query(strategy, &request(Some("  \n"))), "src/lib.rs");
+        }
+    }
+
+    #[test]
+    fn path_only_and_full() {
+        let request = request(Some(CONTENT));
+        assert_eq!(build_query(QueryStrategy::PathOnly, &request), "src/lib.rs");
+        assert_eq!(
+            build_query(QueryStrategy::Full, &request),
+            format!("src/lib.rs {}", CONTENT)
+        );
+    }
+
+    #[test]
+    fn snippet_keeps_first_and_last_lines() {
+        let query = build_query(QueryStrategy::PathPlusSnippet { lines: 1 }, &request(Some(CONTENT)));
+        assert_eq!(query, "src/lib.rs use std::fmt;\nfn render_page() {}");
+
+        let short = build_query(QueryStrategy::PathPlusSnippet { lines: 2 }, &request(Some(CONTENT)));
+        assert_eq!(short, format!("src/lib.rs {}", CONTENT));
+    }
+
+    #[test]
+    fn symbols_skip_keywords_numbers_short_tokens_and_repeats() {
+        let content = "fn parse_config() { let id = 42; parse_config(); Self::render_page() }";
+        let query = build_query(QueryStrategy::Symbols, &request(Some(content)));
+        assert_eq!(query, "src/lib.rs parse_config render_page");
+    }
+
+    #[test]
+    fn symbols_are_capped() {
+        let content: String = (0..MAX_SYMBOLS + 10).map(|i| format!("name_{} ", i)).collect();
+        assert_eq!(symbols(&content).len(), MAX_SYMBOLS);
+    }
+
+    #[test]
+    fn strategy_names_parse() {
+        assert_eq!(QueryStrategy::parse("path_only", 3), QueryStrategy::PathOnly);
+        assert_eq!(
+            QueryStrategy::parse("path_plus_snippet", 3),
+            QueryStrategy::PathPlusSnippet { lines: 3 }
+        );
+        assert_eq!(QueryStrategy::parse("full", 3), QueryStrategy::Full);
+        assert_eq!(QueryStrategy::parse("symbols", 3), QueryStrategy::Symbols);
+    }
+
+    #[test]
+    #[should_panic(expected = "CONTEXT_QUERY_STRATEGY")]
+    fn unknown_strategy_panics() {
+        QueryStrategy::parse("fuzzy", 3);
+    }
+}
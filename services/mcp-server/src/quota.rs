@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Per-user memory quota. The stored count comes from the memory service and
+// is cached for a short TTL; stores made through this server bump the cached
+// count so a burst of logs can't overshoot the cap between refreshes.
+
+pub struct MemoryQuota {
+    counts: Mutex<HashMap<String, (Instant, u64)>>,
+    max_per_user: u64,
+    cache_ttl: Duration,
+}
+
+impl MemoryQuota {
+    /// `max_per_user` of zero disables the quota.
+    pub fn new(max_per_user: u64, cache_ttl: Duration) -> Self {
+        Self {
+            counts: Mutex::new(HashMap::new()),
+            max_per_user,
+            cache_ttl,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_per_user > 0
+    }
+
+    /// Cached count for `user_id`, if still fresh.
+    pub fn cached_count(&self, user_id: &str) -> Option<u64> {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        counts
+            .get(user_id)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.cache_ttl)
+            .map(|(_, count)| *count)
+    }
+
+    pub fn record_count(&self, user_id: &str, count: u64) {
+        let now = Instant::now();
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        counts.retain(|_, (fetched_at, _)| now.duration_since(*fetched_at) < self.cache_ttl);
+        counts.insert(user_id.to_string(), (now, count));
+    }
+
+    /// Account for a store made since the count was fetched.
+    pub fn record_store(&self, user_id: &str) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, count)) = counts.get_mut(user_id) {
+            *count += 1;
+        }
+    }
+
+    pub fn is_exceeded(&self, count: u64) -> bool {
+        self.is_enabled() && count >= self.max_per_user
+    }
+}
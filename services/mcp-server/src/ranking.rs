@@ -0,0 +1,104 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{MemoryExplanation, MemoryItem};
+
+// Local re-ranking helpers applied to memory search results.
+
+/// Re-rank memories so items carrying any of `boost_tags` have their
+/// confidence multiplied by `factor` for ordering purposes. Scores in the
+/// items themselves are left untouched; ties keep their original order.
+pub fn apply_tag_boost(memories: &mut [MemoryItem], boost_tags: &[String], factor: f32) {
+    if boost_tags.is_empty() {
+        return;
+    }
+
+    let boosted_score = |memory: &MemoryItem| {
+        let matches = memory
+            .tags
+            .iter()
+            .any(|tag| boost_tags.iter().any(|boost| boost.eq_ignore_ascii_case(tag)));
+        if matches {
+            memory.confidence_score * factor
+        } else {
+            memory.confidence_score
+        }
+    };
+
+    memories.sort_by(|a, b| {
+        boosted_score(b)
+            .partial_cmp(&boosted_score(a))
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// Fallback explanation for memories the memory service didn't explain:
+/// the confidence score plus the memory's age, when its timestamp parses.
+pub fn synthesize_explanation(memory: &MemoryItem, now: DateTime<Utc>) -> MemoryExplanation {
+    let age_secs = memory
+        .created_at_utc()
+        .map(|created_at| (now - created_at).num_seconds().max(0));
+    MemoryExplanation {
+        synthesized: true,
+        factors: serde_json::json!({
+            "confidence_score": memory.confidence_score,
+            "age_secs": age_secs,
+            "tier": memory.tier,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(id: &str, score: f32, tags: &[&str]) -> MemoryItem {
+        MemoryItem {
+            id: id.to_string(),
+            confidence_score: score,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn ids(memories: &[MemoryItem]) -> Vec<&str> {
+        memories.iter().map(|memory| memory.id.as_str()).collect()
+    }
+
+    #[test]
+    fn boosted_tags_move_memories_up_without_changing_scores() {
+        let mut memories = vec![
+            memory("plain", 0.8, &["edit"]),
+            memory("tagged", 0.5, &["Debug"]),
+        ];
+        apply_tag_boost(&mut memories, &["debug".to_string()], 2.0);
+        assert_eq!(ids(&memories), ["tagged", "plain"]);
+        assert_eq!(memories[0].confidence_score, 0.5);
+    }
+
+    #[test]
+    fn boost_that_does_not_overtake_keeps_the_order() {
+        let mut memories = vec![memory("plain", 0.9, &[]), memory("tagged", 0.5, &["debug"])];
+        apply_tag_boost(&mut memories, &["debug".to_string()], 1.5);
+        assert_eq!(ids(&memories), ["plain", "tagged"]);
+    }
+
+    #[test]
+    fn ties_keep_their_original_order() {
+        let mut memories = vec![
+            memory("first", 0.5, &[]),
+            memory("second", 0.25, &["debug"]),
+            memory("third", 0.5, &[]),
+        ];
+        apply_tag_boost(&mut memories, &["debug".to_string()], 2.0);
+        assert_eq!(ids(&memories), ["first", "second", "third"]);
+    }
+
+    #[test]
+    fn no_boost_tags_leaves_memories_untouched() {
+        let mut memories = vec![memory("low", 0.1, &["debug"]), memory("high", 0.9, &[])];
+        apply_tag_boost(&mut memories, &[], 10.0);
+        assert_eq!(ids(&memories), ["low", "high"]);
+    }
+}
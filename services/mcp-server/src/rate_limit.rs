@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error,
+};
+
+use crate::errors::McpError;
+use crate::metrics;
+
+// Per-user requests-per-minute limits resolved from the user's tenant.
+// Each tenant (or plan tier) maps to an RPM; unknown or missing tenants get
+// the default. Counting uses fixed one-minute windows per user. An RPM of
+// zero means unlimited.
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TenantRateLimits {
+    pub default_rpm: u32,
+    pub tenants: HashMap<String, u32>,
+}
+
+impl TenantRateLimits {
+    /// Parse `tenant=rpm` pairs, e.g. `free=60,pro=600`.
+    pub fn parse(default_rpm: u32, spec: &[String]) -> Self {
+        let tenants = spec
+            .iter()
+            .map(|entry| {
+                let (tenant, rpm) = entry
+                    .split_once('=')
+                    .unwrap_or_else(|| panic!("TENANT_RATE_LIMITS entry '{}' must be tenant=rpm", entry));
+                let rpm: u32 = rpm
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("TENANT_RATE_LIMITS rpm '{}' is not a number", rpm));
+                (tenant.trim().to_string(), rpm)
+            })
+            .collect();
+        Self { default_rpm, tenants }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.default_rpm > 0 || self.tenants.values().any(|rpm| *rpm > 0)
+    }
+
+    pub fn resolve(&self, tenant: Option<&str>) -> u32 {
+        tenant
+            .and_then(|tenant| self.tenants.get(tenant))
+            .copied()
+            .unwrap_or(self.default_rpm)
+    }
+}
+
+pub struct RateLimiter {
+    limits: RwLock<TenantRateLimits>,
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: TenantRateLimits) -> Self {
+        Self {
+            limits: RwLock::new(limits),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Swap in new limits, e.g. after a config reload. Current windows are
+    /// kept, so users are judged against the new limit straight away.
+    pub fn set_limits(&self, limits: TenantRateLimits) {
+        *self.limits.write().unwrap_or_else(|e| e.into_inner()) = limits;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.limits.read().unwrap_or_else(|e| e.into_inner()).is_enabled()
+    }
+
+    /// Count a request for `user_id` against their tenant's limit.
+    pub fn check(&self, user_id: &str, tenant: Option<&str>) -> Result<(), McpError> {
+        let rpm = self
+            .limits
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .resolve(tenant);
+        if rpm == 0 {
+            metrics::observe_rate_limit(rpm, true);
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        windows.retain(|_, (started, _)| now.duration_since(*started) < WINDOW);
+
+        let (started, count) = windows.entry(user_id.to_string()).or_insert((now, 0));
+        if *count >= rpm {
+            metrics::observe_rate_limit(rpm, false);
+            let remaining = WINDOW - now.duration_since(*started);
+            return Err(McpError::TooManyRequests {
+                message: format!("Rate limit of {} requests per minute exceeded", rpm),
+                retry_after_secs: remaining.as_secs().max(1),
+            });
+        }
+        *count += 1;
+        metrics::observe_rate_limit(rpm, true);
+        Ok(())
+    }
+}
+
+/// Middleware applying the rate limiter to identified users. Requests
+/// without a user are left to the handlers to reject. The user id is only
+/// looked up for limited requests, and the lookup is shared with the
+/// handler through the request extensions.
+pub async fn rate_limit<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let limiter = req
+        .app_data::<web::Data<Arc<RateLimiter>>>()
+        .filter(|limiter| {
+            limiter.is_enabled() && !crate::concurrency::UNLIMITED_PATHS.contains(&req.path())
+        })
+        .cloned();
+    let limiter = match limiter {
+        Some(limiter) => limiter,
+        None => return next.call(req).await.map(ServiceResponse::map_into_left_body),
+    };
+    let user_id = match crate::middleware::extract_user_id(req.request()) {
+        Some(user_id) => user_id,
+        None => return next.call(req).await.map(ServiceResponse::map_into_left_body),
+    };
+
+    let tenant = crate::middleware::extract_tenant(req.request());
+    match limiter.check(&user_id, tenant.as_deref()) {
+        Ok(()) => next.call(req).await.map(ServiceResponse::map_into_left_body),
+        Err(err) => Ok(req.error_response(err).map_into_right_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::ResolvedUserId;
+    use actix_web::{
+        http::StatusCode, middleware::from_fn, test, App, HttpMessage, HttpRequest, HttpResponse,
+    };
+
+    /// Answers 200 only when the user id was already resolved upstream.
+    async fn resolved_handler(req: HttpRequest) -> HttpResponse {
+        let resolved = req.extensions().get::<ResolvedUserId>().is_some();
+        match crate::middleware::extract_user_id(&req) {
+            Some(_) if resolved => HttpResponse::Ok().finish(),
+            _ => HttpResponse::InternalServerError().finish(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn limited_requests_share_the_resolved_user_id() {
+        let limiter = Arc::new(RateLimiter::new(TenantRateLimits::parse(1, &[])));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(limiter))
+                .wrap(from_fn(rate_limit))
+                .route("/mcp/test", web::get().to(resolved_handler)),
+        )
+        .await;
+        let request = || {
+            test::TestRequest::get()
+                .uri("/mcp/test")
+                .insert_header(("X-User-Id", "alice"))
+                .to_request()
+        };
+
+        assert_eq!(test::call_service(&app, request()).await.status(), StatusCode::OK);
+        assert_eq!(
+            test::call_service(&app, request()).await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn tenants_resolve_to_their_limit_or_the_default() {
+        let limits = TenantRateLimits::parse(10, &["free=60".to_string(), "pro=600".to_string()]);
+        assert_eq!(limits.resolve(Some("pro")), 600);
+        assert_eq!(limits.resolve(Some("unknown")), 10);
+        assert_eq!(limits.resolve(None), 10);
+    }
+}
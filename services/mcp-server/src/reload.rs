@@ -0,0 +1,48 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::config::Config;
+use crate::rate_limit::RateLimiter;
+use crate::state::RuntimeState;
+
+// Configuration reload on SIGHUP. The .env file is re-read, filling in keys
+// the process environment doesn't set, and the reloadable settings are
+// swapped into RuntimeState; everything else (port, service URLs, pool and
+// cache sizes, secrets) still needs a restart. The process environment is
+// never written to. An invalid configuration leaves the current settings in
+// place.
+
+pub async fn reload_on_sighup(runtime_state: Arc<RuntimeState>, rate_limiter: Arc<RateLimiter>) {
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err) => {
+            log::error!("Failed to install SIGHUP handler; config reload is unavailable: {}", err);
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        log::info!("SIGHUP received, reloading configuration");
+
+        // Config parsing panics on invalid values, which must not take the
+        // server down on a reload
+        let current = runtime_state.config();
+        let fresh = match catch_unwind(AssertUnwindSafe(|| Config::reload_from_env(&current))) {
+            Ok(config) => config,
+            Err(_) => {
+                log::error!("Configuration reload failed; keeping the current settings");
+                continue;
+            }
+        };
+
+        let changed = runtime_state.reload(&fresh);
+        if changed.is_empty() {
+            log::info!("Configuration reloaded; no reloadable settings changed");
+            continue;
+        }
+        rate_limiter.set_limits(runtime_state.config().rate_limits.clone());
+        log::info!("Configuration reloaded; changed: {}", changed.join(", "));
+    }
+}
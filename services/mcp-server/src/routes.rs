@@ -1,30 +1,118 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::http::{header, StatusCode};
+use actix_web::middleware::DefaultHeaders;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError, Result};
+use futures::{stream, StreamExt};
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
 
+use crate::circuit::CircuitState;
+use crate::concurrency::{SessionLocks, UserTaskLimits};
+use crate::config::{Config, MissingSessionPolicy};
+use crate::context_cache::ContextCache;
+use crate::cooldown::UserCooldown;
+use crate::cors;
+use crate::dedup::StoreDedup;
 use crate::errors::McpError;
+use crate::events::EventPublisher;
+use crate::health::HealthMonitor;
+use crate::logging::sampled_info;
 use crate::models::*;
+use crate::pagination::{PaginationParams, SearchCursor, SearchPage, MAX_SEARCH_WINDOW};
+use crate::quota::MemoryQuota;
 use crate::services::{IntelligenceServiceClient, MemoryServiceClient};
 use crate::metrics;
+use crate::state::RuntimeState;
+use crate::task_dedup::TaskDedup;
+use crate::validation::{is_valid_memory_id, Validate};
 
-pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+pub fn configure_routes(cfg: &mut web::ServiceConfig, config: &Config) {
+    // Load balancer ping: outside the /mcp scope so not even CORS runs
+    cfg.route("/mcp/ping", web::get().to(ping));
+    // Streaming endpoints are registered ahead of the /mcp scope so they get
+    // their own CORS policy.
+    cfg.service(
+        web::resource("/mcp/memory/export")
+            .wrap(cors::streaming_cors(config))
+            .route(web::get().to(export_memories)),
+    );
     cfg.service(
         web::scope("/mcp")
+            .wrap(cors::json_cors(config))
             .route("/health", web::get().to(health_check))
+            .route("/readyz", web::get().to(readiness_check))
+            .route("/status", web::get().to(status))
             .route("/context/fetch", web::post().to(fetch_context))
-            .route("/memory/log", web::post().to(log_memory))
-            .route("/task/submit", web::post().to(submit_task))
             .route("/metrics", web::get().to(export_metrics))
+            .route("/auth/validate", web::post().to(validate_auth))
+            // Mutating endpoints: responses must never be replayed from a cache
+            .service(
+                web::resource("/memory/log")
+                    .wrap(no_store())
+                    .route(web::post().to(log_memory)),
+            )
+            .service(
+                web::resource("/memory/import")
+                    .wrap(no_store())
+                    .route(web::post().to(import_memories)),
+            )
+            .service(
+                web::resource("/memory/consolidate")
+                    .wrap(no_store())
+                    .route(web::post().to(consolidate_memories)),
+            )
+            .service(
+                web::resource("/task/submit")
+                    .wrap(no_store())
+                    .route(web::post().to(submit_task)),
+            )
+            .service(
+                web::resource("/admin/read-only")
+                    .wrap(no_store())
+                    .route(web::post().to(set_read_only)),
+            )
+            .service(
+                web::resource("/admin/metrics/reset")
+                    .wrap(no_store())
+                    .route(web::post().to(reset_metrics)),
+            )
+            // Last, so the fixed /memory/* paths above match first
+            .route("/memory/{id}", web::get().to(get_memory))
     );
 }
 
-async fn export_metrics() -> Result<HttpResponse, McpError> {
+/// Marks every response, including errors, as uncacheable.
+fn no_store() -> DefaultHeaders {
+    DefaultHeaders::new().add((header::CACHE_CONTROL, "no-store"))
+}
+
+/// GET /mcp/ping
+/// Cheapest possible liveness check: no auth, logging or metrics
+async fn ping() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body("pong")
+}
+
+async fn export_metrics(req: HttpRequest) -> Result<HttpResponse, McpError> {
     let start = Instant::now();
-    match metrics::gather_metrics() {
+    // Scrapers that prefer OpenMetrics say so in Accept; default to Prometheus text
+    let openmetrics = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| accept.contains("application/openmetrics-text"))
+        .unwrap_or(false);
+    let (encoded, content_type) = if openmetrics {
+        (metrics::gather_openmetrics(), metrics::OPENMETRICS_CONTENT_TYPE)
+    } else {
+        (metrics::gather_metrics(), metrics::PROMETHEUS_CONTENT_TYPE)
+    };
+
+    match encoded {
         Ok(buffer) => {
             metrics::observe_request("/mcp/metrics", "success", start.elapsed());
             Ok(HttpResponse::Ok()
-                .content_type("text/plain; version=0.0.4")
+                .content_type(content_type)
                 .body(buffer))
         }
         Err(err) => {
@@ -42,15 +130,38 @@ async fn export_metrics() -> Result<HttpResponse, McpError> {
 async fn health_check(
     memory_client: web::Data<Arc<MemoryServiceClient>>,
     intelligence_client: web::Data<Arc<IntelligenceServiceClient>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
+    config: web::Data<Config>,
 ) -> Result<HttpResponse, McpError> {
     let start = Instant::now();
-    log::info!("Health check requested");
+    sampled_info!("Health check requested");
 
     let memory_ok = memory_client.health_check().await;
     let intelligence_ok = intelligence_client.health_check().await;
 
+    let circuits = CircuitsStatus {
+        memory_service: memory_client.circuit_state(),
+        intelligence_service: intelligence_client.circuit_state(),
+    };
+    // Optional dependencies never degrade the server, whether down or tripped
+    let circuit_open = (config.memory_service_required
+        && circuits.memory_service == CircuitState::Open)
+        || (config.intelligence_service_required
+            && circuits.intelligence_service == CircuitState::Open);
+    let circuits_ok = !(circuit_open && config.degrade_on_open_circuit);
+
+    let dependencies = DependenciesStatus {
+        memory_service: DependencyStatus::new(memory_ok, config.memory_service_required),
+        intelligence_service: DependencyStatus::new(
+            intelligence_ok,
+            config.intelligence_service_required,
+        ),
+    };
+    let dependencies_ok = dependencies.memory_service != DependencyStatus::DownRequired
+        && dependencies.intelligence_service != DependencyStatus::DownRequired;
+
     let response = HealthResponse {
-        status: if memory_ok && intelligence_ok {
+        status: if dependencies_ok && circuits_ok {
             "healthy".to_string()
         } else {
             "degraded".to_string()
@@ -58,6 +169,9 @@ async fn health_check(
         version: env!("CARGO_PKG_VERSION").to_string(),
         memory_service: memory_ok,
         intelligence_service: intelligence_ok,
+        read_only: runtime_state.is_read_only(),
+        circuits,
+        dependencies,
     };
 
     metrics::observe_request("/mcp/health", "success", start.elapsed());
@@ -65,15 +179,194 @@ async fn health_check(
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// GET /mcp/readyz
+/// Readiness probe answered from the cached background health state
+async fn readiness_check(
+    health_monitor: web::Data<Arc<HealthMonitor>>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, McpError> {
+    let start = Instant::now();
+    let snapshot = health_monitor.snapshot();
+
+    let dependencies = DependenciesStatus {
+        memory_service: DependencyStatus::new(
+            snapshot.memory_service.unwrap_or(false),
+            config.memory_service_required,
+        ),
+        intelligence_service: DependencyStatus::new(
+            snapshot.intelligence_service.unwrap_or(false),
+            config.intelligence_service_required,
+        ),
+    };
+    let ready = snapshot.checked_at.is_some()
+        && dependencies.memory_service != DependencyStatus::DownRequired
+        && dependencies.intelligence_service != DependencyStatus::DownRequired;
+
+    let response = ReadinessResponse {
+        ready,
+        dependencies,
+        checked_at: snapshot.checked_at.map(|checked_at| checked_at.to_rfc3339()),
+    };
+
+    metrics::observe_request("/mcp/readyz", "success", start.elapsed());
+
+    if ready {
+        Ok(HttpResponse::Ok().json(response))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(response))
+    }
+}
+
+/// GET /mcp/status
+/// Uptime and request counters, without scraping Prometheus
+async fn status(
+    health_monitor: web::Data<Arc<HealthMonitor>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
+) -> Result<HttpResponse, McpError> {
+    let start = Instant::now();
+    let snapshot = health_monitor.snapshot();
+
+    let response = StatusResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: runtime_state.uptime().as_secs(),
+        requests_total: runtime_state.requests_total(),
+        requests_in_flight: runtime_state.requests_in_flight(),
+        read_only: runtime_state.is_read_only(),
+        memory_service: snapshot.memory_service,
+        intelligence_service: snapshot.intelligence_service,
+        enabled_endpoints: runtime_state
+            .config()
+            .endpoints
+            .enabled()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    };
+
+    metrics::observe_request("/mcp/status", "success", start.elapsed());
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// POST /mcp/admin/read-only
+/// Toggle read-only maintenance mode (admin only)
+async fn set_read_only(
+    req: HttpRequest,
+    request: web::Json<ReadOnlyRequest>,
+    config: web::Data<Config>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
+) -> Result<HttpResponse, McpError> {
+    let start = Instant::now();
+    let endpoint = "/mcp/admin/read-only";
+    if let Err(err) = crate::middleware::require_admin(&req, &config) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+
+    runtime_state.set_read_only(request.enabled);
+    log::warn!("Read-only maintenance mode set to {}", request.enabled);
+
+    metrics::observe_request(endpoint, "success", start.elapsed());
+
+    Ok(HttpResponse::Ok().json(ReadOnlyResponse {
+        read_only: runtime_state.is_read_only(),
+    }))
+}
+
+/// POST /mcp/admin/metrics/reset
+/// Zero all metrics between load-test runs (admin only, ALLOW_METRICS_RESET)
+async fn reset_metrics(
+    req: HttpRequest,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, McpError> {
+    if !config.allow_metrics_reset {
+        return Err(McpError::NotFound("Metrics reset is disabled".to_string()));
+    }
+    crate::middleware::require_admin(&req, &config)?;
+
+    metrics::reset_metrics();
+    log::warn!("All MCP metrics were reset via admin endpoint");
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// POST /mcp/auth/validate
+/// Check the Bearer token without touching any downstream service
+async fn validate_auth(
+    req: HttpRequest,
+    runtime_state: web::Data<Arc<RuntimeState>>,
+) -> Result<HttpResponse, McpError> {
+    let start = Instant::now();
+    let endpoint = "/mcp/auth/validate";
+    let config = runtime_state.config();
+
+    let response = match crate::middleware::bearer_token(&req) {
+        None => TokenValidationResponse {
+            valid: false,
+            user_id: None,
+            expires_at: None,
+            reason: Some("missing bearer token".to_string()),
+        },
+        Some(token) => match crate::middleware::validate_token(
+            token,
+            &config.jwt_secret,
+            config.jwt_clock_skew_secs,
+        ) {
+            Ok(claims) => TokenValidationResponse {
+                valid: true,
+                user_id: Some(claims.sub),
+                expires_at: chrono::DateTime::from_timestamp(claims.exp as i64, 0)
+                    .map(|expires_at| expires_at.to_rfc3339()),
+                reason: None,
+            },
+            Err(reason) => TokenValidationResponse {
+                valid: false,
+                user_id: None,
+                expires_at: None,
+                reason: Some(reason),
+            },
+        },
+    };
+
+    metrics::observe_request(endpoint, "success", start.elapsed());
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 /// POST /mcp/context/fetch
 /// Fetch relevant context from memory for a file
 async fn fetch_context(
     req: HttpRequest,
-    request: web::Json<ContextFetchRequest>,
+    body: web::Json<serde_json::Value>,
     memory_client: web::Data<Arc<MemoryServiceClient>>,
+    context_cache: web::Data<Arc<ContextCache>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
 ) -> Result<HttpResponse, McpError> {
     let start = Instant::now();
     let endpoint = "/mcp/context/fetch";
+    let config = runtime_state.config();
+    if let Err(err) = config.endpoints.ensure_enabled(endpoint) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    // Accept any supported request schema, converted to the current model
+    let api_version = match crate::api_version::requested_version(&req) {
+        Ok(version) => version,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
+    let mut request =
+        match crate::api_version::context_fetch_request(api_version, body.into_inner())
+            .and_then(|request| request.validate(&config.field_limits).map(|_| request))
+        {
+            Ok(request) => request,
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(err);
+            }
+        };
     // Extract user_id from request
     let user_id = match crate::middleware::extract_user_id(&req) {
         Some(id) => id,
@@ -82,28 +375,223 @@ async fn fetch_context(
             return Err(McpError::Unauthorized("User ID not found in request".to_string()));
         }
     };
+    metrics::observe_user_request(endpoint, &user_id);
 
-    log::info!("Fetching context for file: {} (user: {})", request.file_path, user_id);
+    let timeout = match crate::middleware::request_timeout(&req, &config) {
+        Ok(timeout) => timeout,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
 
-    // Build search query from file path and content
-    let query = if let Some(content) = &request.file_content {
-        format!("{} {}", request.file_path, content)
-    } else {
-        request.file_path.clone()
+    sampled_info!("Fetching context for file: {} (user: {})", request.file_path, user_id);
+
+    // Fill in a missing language from the file extension, so it's treated
+    // exactly like one the client sent
+    let mut inferred_language = None;
+    if request.language.is_none() && config.infer_language_from_path {
+        if let Some(language) = crate::languages::from_path(&request.file_path) {
+            request.language = Some(language.to_string());
+            inferred_language = Some(language.to_string());
+        }
+    }
+
+    // Unknown languages still get context, but the editor is told about it
+    let mut warnings = Vec::new();
+    if let Some(language) = &request.language {
+        let known = config
+            .known_languages
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(language));
+        if !known {
+            warnings.push(format!(
+                "Language '{}' is not recognized; context quality may be reduced",
+                language
+            ));
+        }
+    }
+
+    // The query is rebuilt from the request on every page; a page token only
+    // carries its hash, so it must match the query of the first page
+    let query = crate::query_builder::build_query(config.context_query_strategy, &request);
+    let cursor = match &request.page_token {
+        Some(token) => match SearchCursor::decode(token, &config.pagination_secret, &user_id) {
+            Ok(cursor) if cursor.is_for_query(&query) => cursor,
+            Ok(_) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(McpError::InvalidRequest(
+                    "Invalid page token: issued for a different query".to_string(),
+                ));
+            }
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(err);
+            }
+        },
+        None => match PaginationParams::new(request.limit, None) {
+            Ok(page) => SearchCursor::first_page(
+                &user_id,
+                &query,
+                page,
+                request.boost_tags.clone(),
+                request.team_id.clone(),
+                request.min_confidence.unwrap_or(config.min_context_confidence),
+            ),
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(err);
+            }
+        },
     };
 
-    // Search memories
-    let limit = request.limit.unwrap_or(5);
-    let memories = match memory_client
-        .search_memories(&user_id, &query, Some(limit))
-        .await
-    {
-        Ok(memories) => memories,
-        Err(err) => {
+    // Team scope is re-authorized on every page, so leaving a team takes
+    // effect even for outstanding page tokens. A memory service that can't
+    // search across users would quietly answer with the caller's own
+    // memories, so team scope is refused unless it can.
+    let team_members = match &cursor.team_id {
+        Some(_) if !config.memory_multi_user_search => {
             metrics::observe_request(endpoint, "error", start.elapsed());
-            return Err(err);
+            return Err(McpError::InvalidRequest(
+                "Team scope is not supported by this deployment's memory service".to_string(),
+            ));
+        }
+        Some(team) => match config.teams.members_for(team, &user_id) {
+            Ok(members) => Some(members.to_vec()),
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(err);
+            }
+        },
+        None => None,
+    };
+
+    // Search memories, widening the window while the page is short only
+    // because memories were dropped and the memory service has more
+    let explain = request.explain.unwrap_or(false);
+    let group_by_tier = request.group_by_tier.unwrap_or(false);
+    let mut window = cursor.window();
+    let (page, partial_results) = loop {
+        let search_request = MemorySearchRequest {
+            query: query.clone(),
+            limit: Some(window),
+            tier: None,
+            boost_tags: cursor.boost_tags.clone(),
+            explain: request.explain,
+            user_ids: team_members.clone(),
+        };
+        let results = match memory_client
+            .search_memories(&user_id, &search_request, timeout)
+            .await
+        {
+            Ok(results) => results,
+            Err(McpError::ServiceUnavailable(reason)) if context_cache.is_enabled() => {
+                // Keep the editor working from the last good answer, if recent enough
+                match context_cache.get(&user_id, &cursor, explain) {
+                    Some(mut cached) => {
+                        log::warn!(
+                            "Memory service unavailable, serving cached context: {}",
+                            reason
+                        );
+                        cached.from_cache = true;
+                        cached.stale = true;
+                        cached.warnings = warnings;
+                        cached.inferred_language = inferred_language;
+                        cached.grouped =
+                            group_by_tier.then(|| group_memories_by_tier(&cached.memories));
+                        metrics::observe_request(endpoint, "fallback", start.elapsed());
+                        // Outage answers must not outlive the outage in a proxy cache
+                        return Ok(HttpResponse::Ok()
+                            .insert_header((
+                                crate::api_version::API_VERSION_HEADER,
+                                api_version.to_string(),
+                            ))
+                            .insert_header((header::CACHE_CONTROL, "no-store"))
+                            .insert_header((header::VARY, "X-User-Id"))
+                            .json(cached));
+                    }
+                    None => {
+                        metrics::observe_request(endpoint, "error", start.elapsed());
+                        return Err(optional_service_error(
+                            "Memory service",
+                            config.memory_service_required,
+                            McpError::ServiceUnavailable(reason),
+                        ));
+                    }
+                }
+            }
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(optional_service_error(
+                    "Memory service",
+                    config.memory_service_required,
+                    err,
+                ));
+            }
+        };
+
+        let exhausted = results.memories.len() < window;
+        let mut memories: Vec<MemoryItem> = results
+            .memories
+            .into_iter()
+            .filter(|memory| cursor.includes(memory))
+            .filter(|memory| is_visible_to(memory, &user_id, team_members.as_deref()))
+            .collect();
+        if let Some(boost_tags) = &cursor.boost_tags {
+            crate::ranking::apply_tag_boost(&mut memories, boost_tags, config.tag_boost_factor);
+        }
+        let page = cursor.page(memories);
+        if page.has_more || exhausted || window >= MAX_SEARCH_WINDOW {
+            break (page, results.partial);
         }
+        window = (window * 2).min(MAX_SEARCH_WINDOW);
     };
+    let SearchPage {
+        memories,
+        has_more,
+        filtered_count,
+    } = page;
+    metrics::MCP_CONTEXT_FILTERED_TOTAL.inc_by(filtered_count as u64);
+
+    // Unscored memories would skew the distribution towards zero
+    for memory in memories.iter().filter(|memory| memory.raw_confidence.is_some()) {
+        metrics::observe_context_confidence(memory.confidence_score);
+    }
+
+    let next_page_token = if has_more {
+        match cursor.next_page().encode(&config.pagination_secret) {
+            Ok(token) => Some(token),
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(err);
+            }
+        }
+    } else {
+        None
+    };
+
+    // Flag context where even the newest memory is past the threshold
+    let now = chrono::Utc::now();
+    let newest_memory_age_secs = memories
+        .iter()
+        .filter_map(|memory| memory.created_at_utc())
+        .map(|created_at| (now - created_at).num_seconds().max(0))
+        .min();
+    let stale_context = newest_memory_age_secs
+        .map(|age| age > config.stale_context_threshold_secs)
+        .unwrap_or(false);
+
+    let memories: Vec<MemoryItem> = memories
+        .into_iter()
+        .map(|mut memory| {
+            memory.explanation = match memory.explanation.take() {
+                _ if !explain => None,
+                Some(explanation) => Some(explanation),
+                None => Some(crate::ranking::synthesize_explanation(&memory, now)),
+            };
+            memory
+        })
+        .collect();
 
     // Build context summary
     let context_summary = if memories.is_empty() {
@@ -116,14 +604,57 @@ async fn fetch_context(
         )
     };
 
+    let grouped = group_by_tier.then(|| group_memories_by_tier(&memories));
+
     let response = ContextFetchResponse {
         memories,
         context_summary,
+        partial_results,
+        next_page_token,
+        stale_context,
+        newest_memory_age_secs,
+        from_cache: false,
+        stale: false,
+        warnings,
+        filtered_count,
+        grouped,
+        inferred_language,
     };
+    context_cache.insert(&user_id, &cursor, explain, &response);
 
     metrics::observe_request(endpoint, "success", start.elapsed());
 
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok()
+        .insert_header((crate::api_version::API_VERSION_HEADER, api_version.to_string()))
+        .insert_header((
+            header::CACHE_CONTROL,
+            format!("private, max-age={}", config.context_cache_max_age_secs),
+        ))
+        .insert_header((header::VARY, "X-User-Id"))
+        .json(response))
+}
+
+/// Whether a search result may be shown to `user_id`: their own memories,
+/// or a teammate's in team scope. Memories without a reported owner are
+/// trusted to be scoped by the memory service in user scope only; a team
+/// search could have returned anyone's.
+fn is_visible_to(memory: &MemoryItem, user_id: &str, team_members: Option<&[String]>) -> bool {
+    match &memory.user_id {
+        None => team_members.is_none(),
+        Some(owner) if owner == user_id => true,
+        Some(owner) => team_members
+            .map(|members| members.iter().any(|member| member == owner))
+            .unwrap_or(false),
+    }
+}
+
+/// Memories keyed by tier, keeping their ranked order within each tier.
+fn group_memories_by_tier(memories: &[MemoryItem]) -> BTreeMap<String, Vec<MemoryItem>> {
+    let mut grouped: BTreeMap<String, Vec<MemoryItem>> = BTreeMap::new();
+    for memory in memories {
+        grouped.entry(memory.tier.clone()).or_default().push(memory.clone());
+    }
+    grouped
 }
 
 /// POST /mcp/memory/log
@@ -132,9 +663,26 @@ async fn log_memory(
     req: HttpRequest,
     request: web::Json<MemoryLogRequest>,
     memory_client: web::Data<Arc<MemoryServiceClient>>,
+    store_dedup: web::Data<Arc<StoreDedup>>,
+    memory_quota: web::Data<Arc<MemoryQuota>>,
+    event_publisher: web::Data<Arc<EventPublisher>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
 ) -> Result<HttpResponse, McpError> {
     let start = Instant::now();
     let endpoint = "/mcp/memory/log";
+    let config = runtime_state.config();
+    if let Err(err) = config.endpoints.ensure_enabled(endpoint) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    if let Err(err) = runtime_state.ensure_writable() {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    if let Err(err) = request.validate(&config.field_limits) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
     // Extract user_id from request
     let user_id = match crate::middleware::extract_user_id(&req) {
         Some(id) => id,
@@ -143,8 +691,32 @@ async fn log_memory(
             return Err(McpError::Unauthorized("User ID not found in request".to_string()));
         }
     };
+    metrics::observe_user_request(endpoint, &user_id);
+
+    let timeout = match crate::middleware::request_timeout(&req, &config) {
+        Ok(timeout) => timeout,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
 
-    log::info!(
+    // Validate tags up front so oversized input never reaches the memory service
+    let mut tags = vec![
+        request.action.clone(),
+        "vscode".to_string(),
+        "mcp".to_string(),
+    ];
+    tags.extend(request.tags.iter().flatten().cloned());
+    let tags = match normalize_tags(tags, &config) {
+        Ok(tags) => tags,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
+
+    sampled_info!(
         "Logging memory: {} action on {} (user: {})",
         request.action,
         request.file_path,
@@ -159,25 +731,73 @@ async fn log_memory(
         request.content.as_ref().unwrap_or(&String::new())
     );
 
-    let output_response = request.outcome.as_ref().map(|s| s.as_str());
-    
-    // Prepare tags
-    let tags = Some(vec![
-        request.action.clone(),
-        "vscode".to_string(),
-        "mcp".to_string(),
-    ]);
+    let outcome = match &request.outcome {
+        Some(outcome) => Some(outcome.clone()),
+        None if config.infer_memory_outcome => {
+            infer_outcome(&request.action, request.metadata.as_ref()).map(str::to_string)
+        }
+        None => None,
+    };
+    let output_response = outcome.clone();
+
+    // Low-value actions may be sampled out before reaching the memory service
+    let keep = config
+        .store_sampling
+        .should_store(&request.action, &input_context);
+    metrics::observe_store_sampling(&request.action, keep);
+    if !keep {
+        metrics::observe_request(endpoint, "success", start.elapsed());
+        return Ok(HttpResponse::Ok().json(MemoryLogResponse {
+            memory_id: String::new(),
+            stored: false,
+            message: "sampled out".to_string(),
+        }));
+    }
+
+    let store_request = MemoryStoreRequest {
+        memory_type: "code_interaction".to_string(),
+        input_context,
+        output_response,
+        outcome,
+        tier: "ltm".to_string(),
+        tags: Some(tags),
+    };
+
+    // Repeats of a recently stored interaction are answered with the original
+    let content_hash = crate::dedup::content_hash(&user_id, &store_request);
+    if let Some(memory_id) = store_dedup.existing(content_hash) {
+        metrics::MCP_MEMORY_DEDUP_HITS_TOTAL.inc();
+        metrics::observe_request(endpoint, "success", start.elapsed());
+        return Ok(HttpResponse::Ok().json(MemoryLogResponse {
+            memory_id,
+            stored: false,
+            message: "duplicate".to_string(),
+        }));
+    }
+
+    // Users at their quota are turned away before the store
+    if memory_quota.is_enabled() {
+        let count = match memory_count(&user_id, &memory_client, &memory_quota, timeout).await {
+            Ok(count) => count,
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(err);
+            }
+        };
+        if memory_quota.is_exceeded(count) {
+            metrics::MCP_MEMORY_QUOTA_REJECTIONS_TOTAL.inc();
+            metrics::observe_request(endpoint, "success", start.elapsed());
+            return Ok(HttpResponse::Ok().json(MemoryLogResponse {
+                memory_id: String::new(),
+                stored: false,
+                message: "quota exceeded".to_string(),
+            }));
+        }
+    }
 
     // Store memory
     let memory_id = match memory_client
-        .store_memory(
-            &user_id,
-            "code_interaction",
-            &input_context,
-            output_response,
-            request.outcome.as_deref(),
-            tags,
-        )
+        .store_memory(&user_id, store_request, timeout)
         .await
     {
         Ok(id) => id,
@@ -186,6 +806,9 @@ async fn log_memory(
             return Err(err);
         }
     };
+    store_dedup.remember(content_hash, &memory_id);
+    memory_quota.record_store(&user_id);
+    event_publisher.memory_stored(&user_id, &memory_id, &request.action);
 
     let response = MemoryLogResponse {
         memory_id: memory_id.clone(),
@@ -198,15 +821,497 @@ async fn log_memory(
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// The user's stored memory count for quota checks, cached between fetches.
+async fn memory_count(
+    user_id: &str,
+    memory_client: &MemoryServiceClient,
+    memory_quota: &MemoryQuota,
+    timeout: Option<Duration>,
+) -> Result<u64, McpError> {
+    if let Some(count) = memory_quota.cached_count(user_id) {
+        return Ok(count);
+    }
+    let count = memory_client.count(user_id, timeout).await?;
+    memory_quota.record_count(user_id, count);
+    Ok(count)
+}
+
+/// Trim tags, drop empty and repeated ones, and enforce the configured
+/// count and length limits.
+fn normalize_tags(tags: Vec<String>, config: &Config) -> Result<Vec<String>, McpError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.is_empty() || normalized.iter().any(|existing| existing == tag) {
+            continue;
+        }
+        if tag.chars().count() > config.max_tag_length {
+            return Err(McpError::InvalidRequest(format!(
+                "Tag exceeds {} characters",
+                config.max_tag_length
+            )));
+        }
+        normalized.push(tag.to_string());
+    }
+
+    if normalized.len() > config.max_memory_tags {
+        return Err(McpError::InvalidRequest(format!(
+            "Too many tags: {} (maximum {})",
+            normalized.len(),
+            config.max_memory_tags
+        )));
+    }
+    Ok(normalized)
+}
+
+/// Derive an outcome for "run"/"debug" actions from an exit code in the
+/// metadata (`exit_code` or `exitCode`). Returns `None` when nothing can be
+/// inferred.
+fn infer_outcome(action: &str, metadata: Option<&serde_json::Value>) -> Option<&'static str> {
+    if action != "run" && action != "debug" {
+        return None;
+    }
+    let metadata = metadata?;
+    let exit_code = metadata
+        .get("exit_code")
+        .or_else(|| metadata.get("exitCode"))?
+        .as_i64()?;
+    Some(if exit_code == 0 { "success" } else { "failure" })
+}
+
+const MEMORY_TIERS: [&str; 3] = ["stm", "itm", "ltm"];
+const EXPORT_PAGE_SIZE: usize = 100;
+
+/// GET /mcp/memory/export
+/// Stream all of the user's memories as NDJSON, one downstream page at a time
+async fn export_memories(
+    req: HttpRequest,
+    query: web::Query<MemoryExportQuery>,
+    memory_client: web::Data<Arc<MemoryServiceClient>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
+) -> Result<HttpResponse, McpError> {
+    let start = Instant::now();
+    let endpoint = "/mcp/memory/export";
+    if let Err(err) = runtime_state.config().endpoints.ensure_enabled(endpoint) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    let user_id = match crate::middleware::extract_user_id(&req) {
+        Some(id) => id,
+        None => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(McpError::Unauthorized("User ID not found in request".to_string()));
+        }
+    };
+    metrics::observe_user_request(endpoint, &user_id);
+
+    let tier = query.into_inner().tier;
+    if let Some(tier) = &tier {
+        if !MEMORY_TIERS.contains(&tier.as_str()) {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(McpError::InvalidRequest(format!("Unknown memory tier: {}", tier)));
+        }
+    }
+
+    sampled_info!("Exporting memories (user: {}, tier: {:?})", user_id, tier);
+
+    // Each page is only requested once actix polls for more body, so a slow
+    // client naturally throttles the downstream fetches.
+    let client = memory_client.get_ref().clone();
+    let body = stream::unfold(Some(0usize), move |offset| {
+        let client = client.clone();
+        let user_id = user_id.clone();
+        let tier = tier.clone();
+        async move {
+            let offset = offset?;
+            match client
+                .list_memories(&user_id, tier.as_deref(), EXPORT_PAGE_SIZE, offset)
+                .await
+            {
+                Ok(records) if records.is_empty() => None,
+                Ok(records) => {
+                    let mut chunk = Vec::new();
+                    for record in &records {
+                        if let Err(err) = serde_json::to_writer(&mut chunk, record) {
+                            return Some((Err(McpError::from(err)), None));
+                        }
+                        chunk.push(b'\n');
+                    }
+                    metrics::MCP_MEMORIES_EXPORTED_TOTAL.inc_by(records.len() as u64);
+                    let next = if records.len() < EXPORT_PAGE_SIZE {
+                        None
+                    } else {
+                        Some(offset + records.len())
+                    };
+                    Some((Ok(web::Bytes::from(chunk)), next))
+                }
+                Err(err) => {
+                    log::error!("Memory export aborted at offset {}: {}", offset, err);
+                    Some((Err(err), None))
+                }
+            }
+        }
+    });
+
+    metrics::observe_request(endpoint, "success", start.elapsed());
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header(("Content-Disposition", "attachment; filename=\"memories.ndjson\""))
+        .streaming(body))
+}
+
+/// POST /mcp/memory/import
+/// Restore memories from an NDJSON body with bounded store concurrency
+async fn import_memories(
+    req: HttpRequest,
+    query: web::Query<MemoryImportQuery>,
+    payload: web::Payload,
+    memory_client: web::Data<Arc<MemoryServiceClient>>,
+    memory_quota: web::Data<Arc<MemoryQuota>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
+) -> Result<HttpResponse, McpError> {
+    let start = Instant::now();
+    let endpoint = "/mcp/memory/import";
+    let config = runtime_state.config();
+    if let Err(err) = config.endpoints.ensure_enabled(endpoint) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    let user_id = match crate::middleware::extract_user_id(&req) {
+        Some(id) => id,
+        None => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(McpError::Unauthorized("User ID not found in request".to_string()));
+        }
+    };
+    metrics::observe_user_request(endpoint, &user_id);
+    if let Err(err) = runtime_state.ensure_writable() {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+
+    let fail_fast = query.fail_fast.unwrap_or(false);
+    let (records, malformed) =
+        match read_import_records(payload, config.memory_import_max_bytes, fail_fast).await {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(err);
+            }
+        };
+
+    sampled_info!(
+        "Importing {} memories for user {} ({} malformed lines skipped)",
+        records.len(),
+        user_id,
+        malformed
+    );
+
+    let client = memory_client.get_ref();
+    let quota = memory_quota.get_ref();
+    let user = user_id.as_str();
+    let config = config.as_ref();
+    let mut results: Vec<_> = stream::iter(records.into_iter().enumerate())
+        .map(|(index, record)| async move {
+            let result = import_record(record, user, config, client, quota).await;
+            if let Err(err) = &result {
+                log::warn!("Failed to import memory for user {}: {}", user, err);
+            }
+            (index, result)
+        })
+        .buffer_unordered(config.memory_import_concurrency.max(1))
+        .collect()
+        .await;
+
+    results.sort_by_key(|(index, _)| *index);
+
+    // Malformed lines count as failed items for the aggregate status
+    let statuses: Vec<StatusCode> = results
+        .iter()
+        .map(|(_, result)| match result {
+            Ok(()) => StatusCode::CREATED,
+            Err(err) => err.status_code(),
+        })
+        .chain(std::iter::repeat(StatusCode::BAD_REQUEST).take(malformed))
+        .collect();
+    let status = crate::batch::aggregate_status(&statuses);
+
+    let failures: Vec<BatchItemFailure> = results
+        .into_iter()
+        .filter_map(|(index, result)| {
+            result.err().map(|err| BatchItemFailure {
+                index,
+                status: err.status_code().as_u16(),
+                error: err.to_string(),
+            })
+        })
+        .collect();
+    let response = MemoryImportResponse {
+        succeeded: statuses.len() - malformed - failures.len(),
+        failed: failures.len(),
+        malformed,
+        failures,
+    };
+
+    let outcome = match status {
+        StatusCode::OK => "success",
+        StatusCode::MULTI_STATUS => "partial",
+        _ => "error",
+    };
+    metrics::observe_request(endpoint, outcome, start.elapsed());
+
+    Ok(HttpResponse::build(status).json(response))
+}
+
+/// Store one imported record through the same field limits, tag rules and
+/// quota as /mcp/memory/log.
+async fn import_record(
+    record: MemoryImportRecord,
+    user_id: &str,
+    config: &Config,
+    memory_client: &MemoryServiceClient,
+    memory_quota: &MemoryQuota,
+) -> Result<(), McpError> {
+    record.validate(&config.field_limits)?;
+    let tags = record
+        .tags
+        .map(|tags| normalize_tags(tags, config))
+        .transpose()?;
+
+    if memory_quota.is_enabled() {
+        let count = memory_count(user_id, memory_client, memory_quota, None).await?;
+        if memory_quota.is_exceeded(count) {
+            metrics::MCP_MEMORY_QUOTA_REJECTIONS_TOTAL.inc();
+            return Err(McpError::Forbidden("memory quota exceeded".to_string()));
+        }
+    }
+
+    let store_request = MemoryStoreRequest {
+        memory_type: record.memory_type,
+        input_context: record.input_context,
+        output_response: record.output_response,
+        outcome: record.outcome,
+        tier: record.tier.unwrap_or_else(|| "ltm".to_string()),
+        tags,
+    };
+    memory_client.store_memory(user_id, store_request, None).await?;
+    memory_quota.record_store(user_id);
+    Ok(())
+}
+
+/// Split the payload into NDJSON lines as it arrives, validating each one.
+/// Returns the valid records and the number of malformed lines skipped; with
+/// `fail_fast` the first malformed line aborts the import instead.
+async fn read_import_records(
+    mut payload: web::Payload,
+    max_bytes: usize,
+    fail_fast: bool,
+) -> Result<(Vec<MemoryImportRecord>, usize), McpError> {
+    let mut records = Vec::new();
+    let mut malformed = 0;
+    let mut line_number = 0;
+    let mut received = 0;
+    let mut buffer: Vec<u8> = Vec::new();
+
+    let mut handle_line = |line: &[u8]| -> Result<(), McpError> {
+        line_number += 1;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return Ok(());
+        }
+        match parse_import_record(line) {
+            Ok(record) => records.push(record),
+            Err(reason) if fail_fast => {
+                return Err(McpError::InvalidRequest(format!(
+                    "Malformed import line {}: {}",
+                    line_number, reason
+                )));
+            }
+            Err(reason) => {
+                log::debug!("Skipping malformed import line {}: {}", line_number, reason);
+                malformed += 1;
+            }
+        }
+        Ok(())
+    };
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk
+            .map_err(|e| McpError::InvalidRequest(format!("Failed to read import body: {}", e)))?;
+        received += chunk.len();
+        if received > max_bytes {
+            return Err(McpError::InvalidRequest(format!(
+                "Import body exceeds {} bytes",
+                max_bytes
+            )));
+        }
+        buffer.extend_from_slice(&chunk);
+        while let Some(pos) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            handle_line(&line[..pos])?;
+        }
+    }
+    if !buffer.is_empty() {
+        handle_line(&buffer)?;
+    }
+
+    Ok((records, malformed))
+}
+
+fn parse_import_record(line: &[u8]) -> Result<MemoryImportRecord, String> {
+    let record: MemoryImportRecord = serde_json::from_slice(line).map_err(|e| e.to_string())?;
+    if record.memory_type.trim().is_empty() {
+        return Err("type must not be empty".to_string());
+    }
+    if record.input_context.trim().is_empty() {
+        return Err("input_context must not be empty".to_string());
+    }
+    if let Some(tier) = &record.tier {
+        if !MEMORY_TIERS.contains(&tier.as_str()) {
+            return Err(format!("unknown tier '{}'", tier));
+        }
+    }
+    Ok(record)
+}
+
+/// POST /mcp/memory/consolidate
+/// Consolidate the caller's short-term memories into long-term memory
+async fn consolidate_memories(
+    req: HttpRequest,
+    memory_client: web::Data<Arc<MemoryServiceClient>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
+    consolidate_cooldown: web::Data<Arc<UserCooldown>>,
+) -> Result<HttpResponse, McpError> {
+    let start = Instant::now();
+    let endpoint = "/mcp/memory/consolidate";
+    let config = runtime_state.config();
+    if let Err(err) = config.endpoints.ensure_enabled(endpoint) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    if let Err(err) = runtime_state.ensure_writable() {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    // Consolidation always runs for the authenticated user only
+    let user_id = match crate::middleware::extract_user_id(&req) {
+        Some(id) => id,
+        None => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(McpError::Unauthorized("User ID not found in request".to_string()));
+        }
+    };
+    metrics::observe_user_request(endpoint, &user_id);
+
+    let timeout = match crate::middleware::request_timeout(&req, &config) {
+        Ok(timeout) => timeout,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
+
+    // Consolidation is expensive; allow one run per user per cooldown. The
+    // slot is taken before the call and given back if the run fails.
+    let reservation = match consolidate_cooldown.reserve(&user_id) {
+        Ok(reservation) => reservation,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
+
+    sampled_info!("Consolidating memories for user: {}", user_id);
+
+    let consolidated = match memory_client.consolidate(&user_id, timeout).await {
+        Ok(consolidated) => consolidated,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
+    reservation.commit();
+
+    metrics::observe_request(endpoint, "success", start.elapsed());
+
+    Ok(HttpResponse::Ok().json(MemoryConsolidateResponse { consolidated }))
+}
+
+/// GET /mcp/memory/{id}
+/// Fetch a single memory owned by the requesting user
+async fn get_memory(
+    req: HttpRequest,
+    path: web::Path<String>,
+    memory_client: web::Data<Arc<MemoryServiceClient>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
+) -> Result<HttpResponse, McpError> {
+    let start = Instant::now();
+    let endpoint = "/mcp/memory/{id}";
+    let config = runtime_state.config();
+    if let Err(err) = config.endpoints.ensure_enabled(endpoint) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    let user_id = match crate::middleware::extract_user_id(&req) {
+        Some(id) => id,
+        None => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(McpError::Unauthorized("User ID not found in request".to_string()));
+        }
+    };
+    metrics::observe_user_request(endpoint, &user_id);
+
+    // The id becomes a downstream path segment, so keep it to id characters
+    let memory_id = path.into_inner();
+    if !is_valid_memory_id(&memory_id) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(McpError::InvalidRequest(format!("Invalid memory id: {}", memory_id)));
+    }
+
+    let timeout = match crate::middleware::request_timeout(&req, &config) {
+        Ok(timeout) => timeout,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
+
+    let memory = match memory_client.get_memory(&user_id, &memory_id, timeout).await {
+        Ok(memory) => memory,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
+
+    metrics::observe_request(endpoint, "success", start.elapsed());
+
+    Ok(HttpResponse::Ok().json(memory))
+}
+
 /// POST /mcp/task/submit
 /// Submit a task to the Intelligence Core
 async fn submit_task(
     req: HttpRequest,
     request: web::Json<TaskSubmitRequest>,
     intelligence_client: web::Data<Arc<IntelligenceServiceClient>>,
+    memory_client: web::Data<Arc<MemoryServiceClient>>,
+    session_locks: web::Data<Arc<SessionLocks>>,
+    user_task_limits: web::Data<Arc<UserTaskLimits>>,
+    task_dedup: web::Data<Arc<TaskDedup>>,
+    runtime_state: web::Data<Arc<RuntimeState>>,
 ) -> Result<HttpResponse, McpError> {
     let start = Instant::now();
     let endpoint = "/mcp/task/submit";
+    let config = runtime_state.config();
+    if let Err(err) = config.endpoints.ensure_enabled(endpoint) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
+    if let Err(err) = request.validate(&config.field_limits) {
+        metrics::observe_request(endpoint, "error", start.elapsed());
+        return Err(err);
+    }
     // Extract user_id from request
     let user_id = match crate::middleware::extract_user_id(&req) {
         Some(id) => id,
@@ -215,11 +1320,34 @@ async fn submit_task(
             return Err(McpError::Unauthorized("User ID not found in request".to_string()));
         }
     };
+    metrics::observe_user_request(endpoint, &user_id);
+
+    let timeout = match crate::middleware::request_timeout(&req, &config) {
+        Ok(timeout) => timeout,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
 
-    log::info!("Submitting task for user: {}", user_id);
+    sampled_info!("Submitting task for user: {}", user_id);
 
-    // Build message with file context if provided
-    let message = if let Some(context) = &request.file_context {
+    // Build message from a named server-side template, or with file context if provided
+    let message = if let Some(name) = &request.template {
+        let template = match config.prompt_templates.get(name) {
+            Some(template) => template,
+            None => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(McpError::InvalidRequest(format!("Unknown prompt template: {}", name)));
+            }
+        };
+        crate::prompts::render_template(
+            template,
+            &request.task_description,
+            request.file_context.as_deref(),
+            request.language.as_deref(),
+        )
+    } else if let Some(context) = &request.file_context {
         format!(
             "File Context:\n{}\n\nTask: {}",
             context, request.task_description
@@ -228,25 +1356,336 @@ async fn submit_task(
         request.task_description.clone()
     };
 
-    // Send to intelligence service with memory enabled
-    let result = match intelligence_client
-        .send_message(&user_id, &message, request.session_id, true)
-        .await
-    {
+    // Cap how many tasks a single user can have in flight
+    let _user_task_guard = match user_task_limits.try_acquire(&user_id) {
+        Ok(guard) => guard,
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(err);
+        }
+    };
+
+    // Serialize calls within a session so turns reach the intelligence
+    // service in order
+    let _session_guard = match request.session_id {
+        Some(session_id) => match session_locks.acquire(session_id).await {
+            Ok(guard) => Some(guard),
+            Err(err) => {
+                metrics::observe_request(endpoint, "error", start.elapsed());
+                return Err(err);
+            }
+        },
+        None => None,
+    };
+
+    // Send to intelligence service with memory enabled, unless memory content
+    // is encrypted: its memory context would be (truncated) ciphertext. A
+    // sessionless duplicate of a recent task shares its call instead of
+    // making another.
+    let use_memory = !config.encrypt_memory_content;
+    let send = || {
+        intelligence_client.send_message(
+            &user_id,
+            &message,
+            request.session_id,
+            use_memory,
+            timeout,
+        )
+    };
+    let outcome = if request.session_id.is_none() && task_dedup.is_enabled() {
+        let key = crate::task_dedup::task_key(&user_id, &message);
+        let slot = task_dedup.slot(key);
+        let mut called = false;
+        let outcome = slot
+            .get_or_try_init(|| {
+                called = true;
+                send()
+            })
+            .await
+            .cloned();
+        task_dedup.release(key, &slot);
+        if outcome.is_ok() && !called {
+            metrics::MCP_TASK_DEDUP_HITS_TOTAL.inc();
+        }
+        outcome
+    } else {
+        send().await
+    };
+    let result = match outcome {
         Ok(result) => result,
+        Err(McpError::ServiceUnavailable(reason)) if config.task_fallback_message.is_some() => {
+            log::warn!("Intelligence service unavailable, serving fallback: {}", reason);
+            let response = match fallback_task_response(
+                &config,
+                &memory_client,
+                &user_id,
+                &request,
+                timeout,
+            )
+            .await
+            {
+                Ok(response) => response,
+                Err(err) => {
+                    metrics::observe_request(endpoint, "error", start.elapsed());
+                    return Err(err);
+                }
+            };
+            metrics::observe_request(endpoint, "fallback", start.elapsed());
+            return Ok(HttpResponse::Ok().json(response));
+        }
+        Err(err) => {
+            metrics::observe_request(endpoint, "error", start.elapsed());
+            return Err(optional_service_error(
+                "Intelligence service",
+                config.intelligence_service_required,
+                err,
+            ));
+        }
+    };
+
+    let session_id = match resolve_session_id(
+        result.session_id,
+        request.session_id,
+        config.missing_session_policy,
+    ) {
+        Ok(session_id) => session_id,
         Err(err) => {
             metrics::observe_request(endpoint, "error", start.elapsed());
             return Err(err);
         }
     };
 
+    // Only a total is reported, so the prompt estimate splits it into
+    // input and output tokens for pricing
+    let estimated_tokens = crate::prompts::estimate_tokens(&message);
+    let mut warnings = Vec::new();
+    let estimated_cost_usd = match result.tokens_used {
+        Some(total) if config.model_prices.is_enabled() => {
+            let total = total.max(0) as u64;
+            let input_tokens = (estimated_tokens as u64).min(total);
+            let model = &config.intelligence_model;
+            let cost = config
+                .model_prices
+                .estimate(model, input_tokens, total - input_tokens);
+            if cost.is_none() {
+                warnings.push(format!("No price configured for model '{}'", model));
+            }
+            cost
+        }
+        _ => None,
+    };
+
     let response = TaskSubmitResponse {
-        session_id: result.session_id,
+        session_id,
         response: result.response,
         tokens_used: result.tokens_used,
+        fallback: false,
+        memories: None,
+        prompt_stats: Some(PromptStats {
+            characters: message.chars().count(),
+            estimated_tokens,
+        }),
+        estimated_cost_usd,
+        warnings,
     };
 
     metrics::observe_request(endpoint, "success", start.elapsed());
 
     Ok(HttpResponse::Ok().json(response))
 }
+
+/// Make an outage of an optional dependency explicit, so clients can tell
+/// "not available in this deployment" apart from an unexpected failure.
+fn optional_service_error(service: &str, required: bool, err: McpError) -> McpError {
+    match err {
+        McpError::ServiceUnavailable(reason) if !required => McpError::ServiceUnavailable(format!(
+            "{} is optional in this deployment and currently unavailable: {}",
+            service, reason
+        )),
+        other => other,
+    }
+}
+
+/// The session id to return for a task, applying `policy` when the
+/// intelligence service left it out.
+pub(crate) fn resolve_session_id(
+    returned: String,
+    requested: Option<Uuid>,
+    policy: MissingSessionPolicy,
+) -> Result<String, McpError> {
+    if !returned.trim().is_empty() {
+        return Ok(returned);
+    }
+    log::warn!("Intelligence service response is missing session_id");
+    match policy {
+        MissingSessionPolicy::Reuse => Ok(requested.unwrap_or_else(Uuid::new_v4).to_string()),
+        MissingSessionPolicy::Generate => Ok(Uuid::new_v4().to_string()),
+        MissingSessionPolicy::Fail => Err(McpError::InternalError(
+            "Intelligence service response is missing session_id".to_string(),
+        )),
+    }
+}
+
+/// Opt-in degraded answer for when the intelligence service is unreachable:
+/// the configured fallback message plus whatever related memories we can
+/// still find for the task. There is no session from the intelligence service,
+/// so the session id follows `MISSING_SESSION_ID_POLICY`.
+async fn fallback_task_response(
+    config: &Config,
+    memory_client: &MemoryServiceClient,
+    user_id: &str,
+    request: &TaskSubmitRequest,
+    timeout: Option<Duration>,
+) -> Result<TaskSubmitResponse, McpError> {
+    let session_id = resolve_session_id(
+        String::new(),
+        request.session_id,
+        config.missing_session_policy,
+    )?;
+    let search_request = MemorySearchRequest {
+        query: request.task_description.clone(),
+        limit: Some(5),
+        tier: None,
+        boost_tags: None,
+        explain: None,
+        user_ids: None,
+    };
+    let memories = match memory_client
+        .search_memories(user_id, &search_request, timeout)
+        .await
+    {
+        Ok(results) => Some(results.memories),
+        Err(err) => {
+            log::warn!("Fallback memory lookup failed: {}", err);
+            None
+        }
+    };
+
+    Ok(TaskSubmitResponse {
+        session_id,
+        response: config.task_fallback_message.clone().unwrap_or_default(),
+        tokens_used: None,
+        fallback: true,
+        memories,
+        prompt_stats: None,
+        estimated_cost_usd: None,
+        warnings: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_owned_by(owner: Option<&str>) -> MemoryItem {
+        MemoryItem {
+            id: "m1".to_string(),
+            user_id: owner.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn user_scope_shows_own_and_unowned_memories() {
+        assert!(is_visible_to(&memory_owned_by(Some("alice")), "alice", None));
+        assert!(is_visible_to(&memory_owned_by(None), "alice", None));
+        assert!(!is_visible_to(&memory_owned_by(Some("bob")), "alice", None));
+    }
+
+    #[test]
+    fn team_scope_drops_outsiders_and_unowned_memories() {
+        let team = vec!["alice".to_string(), "bob".to_string()];
+        assert!(is_visible_to(&memory_owned_by(Some("bob")), "alice", Some(&team)));
+        assert!(!is_visible_to(&memory_owned_by(Some("carol")), "alice", Some(&team)));
+        assert!(!is_visible_to(&memory_owned_by(None), "alice", Some(&team)));
+    }
+
+    #[test]
+    fn run_and_debug_outcomes_follow_the_exit_code() {
+        let passed = serde_json::json!({ "exit_code": 0 });
+        let failed = serde_json::json!({ "exitCode": 101 });
+        assert_eq!(infer_outcome("run", Some(&passed)), Some("success"));
+        assert_eq!(infer_outcome("debug", Some(&failed)), Some("failure"));
+    }
+
+    #[test]
+    fn outcome_is_not_inferred_without_a_usable_exit_code() {
+        let exit_zero = serde_json::json!({ "exit_code": 0 });
+        assert_eq!(infer_outcome("edit", Some(&exit_zero)), None);
+        assert_eq!(infer_outcome("run", None), None);
+        assert_eq!(infer_outcome("run", Some(&serde_json::json!({}))), None);
+        assert_eq!(infer_outcome("run", Some(&serde_json::json!({ "exit_code": "0" }))), None);
+    }
+
+    async fn scrape_metrics(accept: Option<&str>) -> (String, String) {
+        use actix_web::{test, App};
+
+        let app = test::init_service(
+            App::new().route("/mcp/metrics", web::get().to(export_metrics)),
+        )
+        .await;
+        let mut req = test::TestRequest::get().uri("/mcp/metrics");
+        if let Some(accept) = accept {
+            req = req.insert_header((header::ACCEPT, accept));
+        }
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let content_type = res.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+        let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+        (content_type, body)
+    }
+
+    #[actix_web::test]
+    async fn metrics_default_to_prometheus_text() {
+        let (content_type, body) = scrape_metrics(None).await;
+        assert_eq!(content_type, metrics::PROMETHEUS_CONTENT_TYPE);
+        assert!(!body.contains("# EOF"));
+    }
+
+    #[actix_web::test]
+    async fn metrics_serve_openmetrics_when_accepted() {
+        let accept = "application/openmetrics-text; version=1.0.0,text/plain;q=0.5";
+        let (content_type, body) = scrape_metrics(Some(accept)).await;
+        assert_eq!(content_type, metrics::OPENMETRICS_CONTENT_TYPE);
+        assert!(body.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn returned_session_id_is_kept_under_every_policy() {
+        let requested = Some(Uuid::new_v4());
+        for policy in [
+            MissingSessionPolicy::Reuse,
+            MissingSessionPolicy::Generate,
+            MissingSessionPolicy::Fail,
+        ] {
+            let session = resolve_session_id("returned".to_string(), requested, policy).unwrap();
+            assert_eq!(session, "returned");
+        }
+    }
+
+    #[test]
+    fn missing_session_id_reuses_the_requested_one() {
+        let requested = Uuid::new_v4();
+        let session =
+            resolve_session_id("  ".to_string(), Some(requested), MissingSessionPolicy::Reuse);
+        assert_eq!(session.unwrap(), requested.to_string());
+
+        let fresh = resolve_session_id(String::new(), None, MissingSessionPolicy::Reuse).unwrap();
+        assert!(Uuid::parse_str(&fresh).is_ok());
+    }
+
+    #[test]
+    fn missing_session_id_is_generated_or_fails() {
+        let requested = Uuid::new_v4();
+        let generated =
+            resolve_session_id(String::new(), Some(requested), MissingSessionPolicy::Generate)
+                .unwrap();
+        assert_ne!(generated, requested.to_string());
+        assert!(Uuid::parse_str(&generated).is_ok());
+
+        assert!(matches!(
+            resolve_session_id(String::new(), Some(requested), MissingSessionPolicy::Fail),
+            Err(McpError::InternalError(_))
+        ));
+    }
+}
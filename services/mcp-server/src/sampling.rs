@@ -0,0 +1,56 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+// Store sampling for logged memories.
+// Each action has a keep rate in [0, 1]; actions without a configured rate
+// are always stored. With `deterministic` the decision is derived from the
+// content hash, so identical interactions are consistently kept or dropped.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoreSamplingPolicy {
+    pub rates: HashMap<String, f64>,
+    pub deterministic: bool,
+}
+
+impl StoreSamplingPolicy {
+    /// Parse `action=rate` pairs, e.g. `edit=0.2,save=0.5`.
+    pub fn parse(spec: &[String], deterministic: bool) -> Self {
+        let rates = spec
+            .iter()
+            .map(|entry| {
+                let (action, rate) = entry
+                    .split_once('=')
+                    .unwrap_or_else(|| panic!("MEMORY_STORE_SAMPLE_RATES entry '{}' must be action=rate", entry));
+                let rate: f64 = rate
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("MEMORY_STORE_SAMPLE_RATES rate '{}' is not a number", rate));
+                (action.trim().to_string(), rate.clamp(0.0, 1.0))
+            })
+            .collect();
+        Self { rates, deterministic }
+    }
+
+    pub fn should_store(&self, action: &str, content: &str) -> bool {
+        let rate = match self.rates.get(action) {
+            Some(rate) => *rate,
+            None => return true,
+        };
+        if rate >= 1.0 {
+            return true;
+        }
+        if rate <= 0.0 {
+            return false;
+        }
+
+        let roll = if self.deterministic {
+            let mut hasher = DefaultHasher::new();
+            content.hash(&mut hasher);
+            (hasher.finish() % 10_000) as f64 / 10_000.0
+        } else {
+            rand::random::<f64>()
+        };
+        roll < rate
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::process::Command;
+
+// Pluggable sources for sensitive configuration values.
+// For a value NAME the source is chosen by which variable is set:
+//   NAME_FILE    -> read from a file (e.g. a mounted secret)
+//   NAME_COMMAND -> stdout of a shell command (e.g. a secrets manager CLI)
+//   NAME         -> plain environment variable (dev default)
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSource {
+    /// The value of NAME itself, if set
+    Env(Option<String>),
+    File(String),
+    Command(String),
+}
+
+impl SecretSource {
+    /// Pick the source for `name`, looking variables up through `var`.
+    pub fn from_vars(name: &str, var: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(path) = var(&format!("{}_FILE", name)) {
+            return SecretSource::File(path);
+        }
+        if let Some(command) = var(&format!("{}_COMMAND", name)) {
+            return SecretSource::Command(command);
+        }
+        SecretSource::Env(var(name))
+    }
+
+    /// Resolve the secret. `Ok(None)` means the source is simply unset;
+    /// a configured file or command that fails is an error.
+    pub fn resolve(&self) -> Result<Option<String>> {
+        let value = match self {
+            SecretSource::Env(value) => match value {
+                Some(value) => value.clone(),
+                None => return Ok(None),
+            },
+            SecretSource::File(path) => fs::read_to_string(path)
+                .with_context(|| format!("failed to read secret file {}", path))?,
+            SecretSource::Command(command) => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .output()
+                    .with_context(|| format!("failed to run secret command `{}`", command))?;
+                if !output.status.success() {
+                    bail!("secret command `{}` exited with {}", command, output.status);
+                }
+                String::from_utf8(output.stdout)
+                    .context("secret command produced non-UTF-8 output")?
+            }
+        };
+
+        let value = value.trim().to_string();
+        Ok(if value.is_empty() { None } else { Some(value) })
+    }
+}
@@ -1,140 +1,765 @@
+use crate::circuit::{CircuitBreaker, CircuitState};
+use crate::downstream;
 use crate::errors::McpError;
+use crate::field_crypto::{self, FieldCipher};
+use crate::hedging::{HedgePolicy, Hedger};
 use crate::models::*;
-use reqwest::{Client, StatusCode};
+use crate::wire::{self, WireFormat};
+use futures::future::join_all;
+use reqwest::{Client, Response, StatusCode};
 use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::Duration;
+
+const DEFAULT_CIRCUIT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_CIRCUIT_RESET_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 10 * 1024 * 1024;
+const DEFAULT_CONFIDENCE_SCALE: f64 = 1.0;
+/// Short-term memories listed per page when consolidating.
+const CONSOLIDATE_PAGE_SIZE: usize = 100;
+/// Most memories one consolidation promotes; the rest wait for the next run.
+const MAX_CONSOLIDATED: usize = 1_000;
+/// Back-off suggested to clients when a downstream 429 names none.
+const DEFAULT_DOWNSTREAM_RETRY_AFTER_SECS: u64 = 60;
+/// Largest downstream error body read for its `detail`.
+const MAX_ERROR_BODY_BYTES: usize = 16 * 1024;
+/// Memory record fields sealed by content encryption.
+const ENCRYPTED_FIELDS: [&str; 2] = ["input_context", "output_response"];
 
 pub struct MemoryServiceClient {
     base_url: String,
+    path_prefix: String,
+    region_urls: Vec<String>,
+    replica_urls: Vec<String>,
+    next_replica: AtomicUsize,
+    hedger: Hedger,
     client: Client,
+    circuit: CircuitBreaker,
+    default_timeout: Duration,
+    health_timeout: Duration,
+    max_response_bytes: usize,
+    wire_format: WireFormat,
+    confidence_scale: f64,
+    normalize_timestamps: bool,
+    content_cipher: Option<FieldCipher>,
+    encrypt_content: bool,
 }
 
 impl MemoryServiceClient {
     pub fn new(base_url: &str) -> Self {
         Self {
             base_url: base_url.to_string(),
+            path_prefix: String::new(),
+            region_urls: Vec::new(),
+            replica_urls: Vec::new(),
+            next_replica: AtomicUsize::new(0),
+            hedger: Hedger::new(HedgePolicy::disabled()),
             client: Client::new(),
+            circuit: CircuitBreaker::new(
+                "memory_service",
+                DEFAULT_CIRCUIT_FAILURE_THRESHOLD,
+                DEFAULT_CIRCUIT_RESET_TIMEOUT,
+            ),
+            default_timeout: DEFAULT_REQUEST_TIMEOUT,
+            health_timeout: DEFAULT_HEALTH_CHECK_TIMEOUT,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            wire_format: WireFormat::Json,
+            confidence_scale: DEFAULT_CONFIDENCE_SCALE,
+            normalize_timestamps: true,
+            content_cipher: None,
+            encrypt_content: false,
+        }
+    }
+
+    /// Timeout applied to calls that don't carry a per-request override.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// Upper end of the memory service's confidence scale, used to
+    /// normalize scores to 0-1.
+    pub fn with_confidence_scale(mut self, scale: f64) -> Self {
+        self.confidence_scale = scale;
+        self
+    }
+
+    /// Rewrite memory timestamps as RFC 3339 UTC (the default), or pass
+    /// them through as the memory service sent them.
+    pub fn with_timestamp_normalization(mut self, enabled: bool) -> Self {
+        self.normalize_timestamps = enabled;
+        self
+    }
+
+    /// Timeout for `health_check`, kept short so probes fail fast.
+    pub fn with_health_timeout(mut self, timeout: Duration) -> Self {
+        self.health_timeout = timeout;
+        self
+    }
+
+    /// Largest response body accepted before the call fails.
+    pub fn with_max_response_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_response_bytes = max_bytes;
+        self
+    }
+
+    /// Encoding for the calls that support protobuf; JSON by default.
+    pub fn with_wire_format(mut self, format: WireFormat) -> Self {
+        self.wire_format = format;
+        self
+    }
+
+    /// Decrypt stored content with `cipher`, and encrypt new content too when
+    /// `encrypt_writes` is set. Keeping the cipher without encrypting writes
+    /// lets a deployment still read what it encrypted earlier.
+    pub fn with_content_encryption(mut self, cipher: Option<FieldCipher>, encrypt_writes: bool) -> Self {
+        self.encrypt_content = encrypt_writes && cipher.is_some();
+        self.content_cipher = cipher;
+        self
+    }
+
+    /// Limit on establishing the TCP/TLS connection, separate from the
+    /// overall request timeout so slow connects fail fast while long reads
+    /// may continue.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.client = Client::builder()
+            .connect_timeout(timeout)
+            .build()
+            .expect("failed to build HTTP client");
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        self.circuit = CircuitBreaker::new("memory_service", failure_threshold, reset_timeout);
+        self
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        self.circuit.state()
+    }
+
+    /// Path prefix the memory service is mounted under (e.g. `/api/v1`),
+    /// shared by all regions. Validated by `Config`.
+    pub fn with_path_prefix(mut self, path_prefix: &str) -> Self {
+        self.path_prefix = path_prefix.to_string();
+        self
+    }
+
+    /// Additional regional memory services searched alongside the primary.
+    pub fn with_regions(mut self, region_urls: Vec<String>) -> Self {
+        self.region_urls = region_urls
+            .into_iter()
+            .filter(|url| url != &self.base_url)
+            .collect();
+        self
+    }
+
+    /// Hedge the primary's opted-in reads to `replica_urls` (round-robin).
+    /// Hedging stays off without replicas; a second request to the same
+    /// primary would only add load to the server that is already slow.
+    pub fn with_hedging(mut self, policy: HedgePolicy, replica_urls: Vec<String>) -> Self {
+        if replica_urls.is_empty() {
+            return self;
         }
+        self.hedger = Hedger::new(policy);
+        self.replica_urls = replica_urls;
+        self
+    }
+
+    /// Where the next hedged attempt goes. Only called when a hedge is sent,
+    /// which requires replicas.
+    fn hedge_target(&self) -> &str {
+        let next = self.next_replica.fetch_add(1, AtomicOrdering::Relaxed);
+        &self.replica_urls[next % self.replica_urls.len()]
     }
 
+    /// Encrypt the content fields of a memory about to be stored.
+    fn seal_content(&self, mut request: MemoryStoreRequest) -> Result<MemoryStoreRequest, McpError> {
+        let cipher = match &self.content_cipher {
+            Some(cipher) if self.encrypt_content => cipher,
+            _ => return Ok(request),
+        };
+        request.input_context = cipher.encrypt("input_context", &request.input_context)?;
+        if let Some(output) = &request.output_response {
+            request.output_response = Some(cipher.encrypt("output_response", output)?);
+        }
+        Ok(request)
+    }
+
+    /// Decrypt the encrypted content fields of a memory record in place.
+    /// Plaintext fields are left alone.
+    fn open_content(&self, record: &mut Value) -> Result<(), McpError> {
+        let cipher = match &self.content_cipher {
+            Some(cipher) => cipher,
+            None => return Ok(()),
+        };
+        for field in ENCRYPTED_FIELDS {
+            let plaintext = match record[field].as_str() {
+                Some(value) if field_crypto::is_encrypted(value) => cipher.decrypt(field, value)?,
+                _ => continue,
+            };
+            record[field] = Value::String(plaintext);
+        }
+        Ok(())
+    }
+
+    /// Search the primary memory service, hedged when `search` is opted in.
+    async fn search_primary(
+        &self,
+        user_id: &str,
+        request_body: &MemorySearchRequest,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<MemoryItem>, McpError> {
+        self.hedger
+            .run("search", &self.base_url, || self.hedge_target(), |url| {
+                self.search_region(url, user_id, request_body, timeout)
+            })
+            .await
+    }
+
+    /// Search the primary memory service and, when configured, every regional
+    /// one concurrently. Results are deduplicated by id (keeping the highest
+    /// confidence), ranked by confidence and cut to `limit`. Regional failures
+    /// are tolerated and reported through `partial`; only when every region
+    /// fails is an error returned.
+    ///
+    /// Refused while content encryption is on: the memory service ranks by
+    /// embeddings of `input_context`, which would then be of ciphertext.
     pub async fn search_memories(
         &self,
         user_id: &str,
-        query: &str,
-        limit: Option<usize>,
+        request_body: &MemorySearchRequest,
+        timeout: Option<Duration>,
+    ) -> Result<MemorySearchResults, McpError> {
+        if self.encrypt_content {
+            return Err(McpError::ServiceUnavailable(
+                "Memory search is unavailable while memory content is encrypted".to_string(),
+            ));
+        }
+        self.circuit
+            .call(self.search_all_regions(user_id, request_body, timeout))
+            .await
+    }
+
+    async fn search_all_regions(
+        &self,
+        user_id: &str,
+        request_body: &MemorySearchRequest,
+        timeout: Option<Duration>,
+    ) -> Result<MemorySearchResults, McpError> {
+        if self.region_urls.is_empty() {
+            let memories = self.search_primary(user_id, request_body, timeout).await?;
+            return Ok(MemorySearchResults { memories, partial: false });
+        }
+
+        let urls: Vec<&String> = std::iter::once(&self.base_url)
+            .chain(self.region_urls.iter())
+            .collect();
+        let results = join_all(urls.iter().map(|url| async move {
+            if *url == &self.base_url {
+                self.search_primary(user_id, request_body, timeout).await
+            } else {
+                self.search_region(url, user_id, request_body, timeout).await
+            }
+        }))
+        .await;
+
+        let mut merged: HashMap<String, MemoryItem> = HashMap::new();
+        let mut last_error = None;
+        let mut failures = 0;
+        for (url, result) in urls.iter().zip(results) {
+            match result {
+                Ok(memories) => {
+                    for memory in memories {
+                        let keep_existing = merged
+                            .get(&memory.id)
+                            .map(|existing| existing.confidence_score >= memory.confidence_score)
+                            .unwrap_or(false);
+                        if !keep_existing {
+                            merged.insert(memory.id.clone(), memory);
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::warn!("Memory search failed for region {}: {}", url, err);
+                    failures += 1;
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        if failures == urls.len() {
+            return Err(last_error.unwrap_or_else(|| {
+                McpError::ServiceUnavailable("All memory regions failed".to_string())
+            }));
+        }
+
+        let mut memories: Vec<MemoryItem> = merged.into_values().collect();
+        memories.sort_by(|a, b| {
+            b.confidence_score
+                .partial_cmp(&a.confidence_score)
+                .unwrap_or(Ordering::Equal)
+        });
+        if let Some(limit) = request_body.limit {
+            memories.truncate(limit);
+        }
+
+        Ok(MemorySearchResults {
+            memories,
+            partial: failures > 0,
+        })
+    }
+
+    async fn search_region(
+        &self,
+        base_url: &str,
+        user_id: &str,
+        request_body: &MemorySearchRequest,
+        timeout: Option<Duration>,
     ) -> Result<Vec<MemoryItem>, McpError> {
-        let url = format!("{}/memory/search", self.base_url);
-        
-        let request_body = MemorySearchRequest {
-            query: query.to_string(),
-            limit,
-            tier: None,
-        };
+        let url = format!("{}{}/memory/search", base_url, self.path_prefix);
 
-        let response = self.client
-            .post(&url)
-            .header("X-User-Id", user_id)
-            .json(&request_body)
-            .send()
-            .await?;
+        let response = downstream::send(
+            "memory_service",
+            &url,
+            wire::with_body::<_, wire::proto::MemorySearchRequest>(
+                self.client
+                    .post(&url)
+                    .timeout(timeout.unwrap_or(self.default_timeout))
+                    .header("X-User-Id", user_id),
+                self.wire_format,
+                request_body,
+            ),
+        )
+        .await?;
 
         if response.status() != StatusCode::OK {
-            return Err(McpError::ServiceUnavailable(
-                format!("Memory service returned status: {}", response.status())
-            ));
+            return Err(status_error("Memory service", response).await);
         }
 
-        let result: Value = response.json().await.map_err(|e| {
-            McpError::InternalError(format!("Failed to parse memory response: {}", e))
-        })?;
+        let protobuf = wire::is_protobuf(&response);
+        let body = read_complete_body(response, "Memory service", self.max_response_bytes).await?;
+        let mut result: Value = if protobuf {
+            wire::decode_search_response(&body)?
+        } else {
+            serde_json::from_slice(&body).map_err(|e| {
+                McpError::InternalError(format!("Failed to parse memory response: {}", e))
+            })?
+        };
 
-        // Parse memories from response
+        // Parse memories from response; one undecryptable record shouldn't
+        // fail the whole search
         let memories = result["results"]
-            .as_array()
+            .as_array_mut()
             .ok_or_else(|| McpError::InternalError("Invalid memory response format".to_string()))?
-            .iter()
-            .map(|m| MemoryItem {
-                id: m["id"].as_str().unwrap_or_default().to_string(),
-                content: format!(
-                    "Input: {}\nOutput: {}",
-                    m["input_context"].as_str().unwrap_or(""),
-                    m["output_response"].as_str().unwrap_or("")
-                ),
-                tier: m["tier"].as_str().unwrap_or("ltm").to_string(),
-                confidence_score: m["confidence_score"].as_f64().unwrap_or(0.0) as f32,
-                created_at: m["created_at"].as_str().unwrap_or_default().to_string(),
+            .iter_mut()
+            .filter_map(|m| match self.open_content(m) {
+                Ok(()) => Some(memory_item_from_value(
+                    m,
+                    self.confidence_scale,
+                    self.normalize_timestamps,
+                )),
+                Err(err) => {
+                    log::warn!("Skipping memory {}: {}", m["id"].as_str().unwrap_or("-"), err);
+                    None
+                }
             })
             .collect();
 
         Ok(memories)
     }
 
+    /// Fetch one memory by id. Memories owned by another user are reported
+    /// as not found, even if the memory service returns them.
+    pub async fn get_memory(
+        &self,
+        user_id: &str,
+        memory_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<MemoryItem, McpError> {
+        let read = self.hedger.run("get", &self.base_url, || self.hedge_target(), |url| {
+            self.get_memory_inner(url, user_id, memory_id, timeout)
+        });
+        self.circuit.call(read).await
+    }
+
+    async fn get_memory_inner(
+        &self,
+        base_url: &str,
+        user_id: &str,
+        memory_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<MemoryItem, McpError> {
+        let url = format!("{}{}/memory/retrieve/{}", base_url, self.path_prefix, memory_id);
+
+        let response = downstream::send(
+            "memory_service",
+            &url,
+            self.client
+                .get(&url)
+                .timeout(timeout.unwrap_or(self.default_timeout))
+                .header("X-User-Id", user_id),
+        )
+        .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(McpError::NotFound(format!("Memory {} not found", memory_id)));
+        }
+        if response.status() != StatusCode::OK {
+            return Err(status_error("Memory service", response).await);
+        }
+
+        let body = read_complete_body(response, "Memory service", self.max_response_bytes).await?;
+        let mut result: Value = serde_json::from_slice(&body).map_err(|e| {
+            McpError::InternalError(format!("Failed to parse memory response: {}", e))
+        })?;
+
+        // Don't rely on the memory service's scoping alone
+        ensure_owned_by(&result, user_id, memory_id)?;
+
+        self.open_content(&mut result)?;
+        Ok(memory_item_from_value(
+            &result,
+            self.confidence_scale,
+            self.normalize_timestamps,
+        ))
+    }
+
     pub async fn store_memory(
         &self,
         user_id: &str,
-        memory_type: &str,
-        input_context: &str,
-        output_response: Option<&str>,
-        outcome: Option<&str>,
-        tags: Option<Vec<String>>,
+        request_body: MemoryStoreRequest,
+        timeout: Option<Duration>,
     ) -> Result<String, McpError> {
-        let url = format!("{}/memory/store", self.base_url);
-        
-        let request_body = MemoryStoreRequest {
-            memory_type: memory_type.to_string(),
-            input_context: input_context.to_string(),
-            output_response: output_response.map(|s| s.to_string()),
-            outcome: outcome.map(|s| s.to_string()),
-            tier: "ltm".to_string(),
-            tags,
-        };
+        self.circuit
+            .call(self.store_memory_inner(user_id, request_body, timeout))
+            .await
+    }
 
-        let response = self.client
-            .post(&url)
-            .header("X-User-Id", user_id)
-            .json(&request_body)
-            .send()
-            .await?;
+    async fn store_memory_inner(
+        &self,
+        user_id: &str,
+        request_body: MemoryStoreRequest,
+        timeout: Option<Duration>,
+    ) -> Result<String, McpError> {
+        let url = format!("{}{}/memory/store", self.base_url, self.path_prefix);
+        let request_body = self.seal_content(request_body)?;
+
+        let response = downstream::send(
+            "memory_service",
+            &url,
+            wire::with_body::<_, wire::proto::MemoryStoreRequest>(
+                self.client
+                    .post(&url)
+                    .timeout(timeout.unwrap_or(self.default_timeout))
+                    .header("X-User-Id", user_id),
+                self.wire_format,
+                &request_body,
+            ),
+        )
+        .await?;
 
         if response.status() != StatusCode::OK && response.status() != StatusCode::CREATED {
-            return Err(McpError::ServiceUnavailable(
-                format!("Memory service returned status: {}", response.status())
-            ));
+            return Err(status_error("Memory service", response).await);
         }
 
-        let result: Value = response.json().await.map_err(|e| {
+        let protobuf = wire::is_protobuf(&response);
+        let body = read_complete_body(response, "Memory service", self.max_response_bytes).await?;
+        if protobuf {
+            return wire::decode_store_response(&body);
+        }
+        let result: Value = serde_json::from_slice(&body).map_err(|e| {
             McpError::InternalError(format!("Failed to parse store response: {}", e))
         })?;
 
         Ok(result["id"].as_str().unwrap_or_default().to_string())
     }
 
+    /// Promote the user's short-term memories into long-term memory.
+    /// Returns how many memories were promoted.
+    pub async fn consolidate(
+        &self,
+        user_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<u64, McpError> {
+        self.circuit
+            .call(self.consolidate_inner(user_id, timeout))
+            .await
+    }
+
+    async fn consolidate_inner(
+        &self,
+        user_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<u64, McpError> {
+        // The memory service has no bulk consolidation, so list the short-term
+        // memories first (promoting while paging would shift the offsets) and
+        // then promote them one by one.
+        let mut memory_ids = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = self
+                .list_memories_inner(user_id, Some("stm"), CONSOLIDATE_PAGE_SIZE, offset)
+                .await?;
+            offset += page.len();
+            memory_ids.extend(
+                page.iter()
+                    .filter_map(|memory| memory["id"].as_str().map(|id| id.to_string())),
+            );
+            if page.len() < CONSOLIDATE_PAGE_SIZE || memory_ids.len() >= MAX_CONSOLIDATED {
+                break;
+            }
+        }
+        memory_ids.truncate(MAX_CONSOLIDATED);
+
+        let mut consolidated = 0;
+        for memory_id in &memory_ids {
+            match self.promote_inner(user_id, memory_id, "ltm", timeout).await {
+                Ok(()) => consolidated += 1,
+                // Expired or deleted since it was listed
+                Err(McpError::NotFound(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(consolidated)
+    }
+
+    async fn promote_inner(
+        &self,
+        user_id: &str,
+        memory_id: &str,
+        target_tier: &str,
+        timeout: Option<Duration>,
+    ) -> Result<(), McpError> {
+        let url = format!(
+            "{}{}/memory/promote/{}",
+            self.base_url, self.path_prefix, memory_id
+        );
+
+        let response = downstream::send(
+            "memory_service",
+            &url,
+            self.client
+                .post(&url)
+                .timeout(timeout.unwrap_or(self.default_timeout))
+                .header("X-User-Id", user_id)
+                .json(&serde_json::json!({ "target_tier": target_tier })),
+        )
+        .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(McpError::NotFound(format!("Memory {} not found", memory_id)));
+        }
+        if response.status() != StatusCode::OK {
+            return Err(status_error("Memory service", response).await);
+        }
+        Ok(())
+    }
+
+    /// Number of memories the memory service holds for the user, from its
+    /// per-user stats.
+    pub async fn count(&self, user_id: &str, timeout: Option<Duration>) -> Result<u64, McpError> {
+        self.circuit.call(self.count_inner(user_id, timeout)).await
+    }
+
+    async fn count_inner(&self, user_id: &str, timeout: Option<Duration>) -> Result<u64, McpError> {
+        let url = format!("{}{}/memory/stats", self.base_url, self.path_prefix);
+
+        let response = downstream::send(
+            "memory_service",
+            &url,
+            self.client
+                .get(&url)
+                .timeout(timeout.unwrap_or(self.default_timeout))
+                .header("X-User-Id", user_id),
+        )
+        .await?;
+
+        if response.status() != StatusCode::OK {
+            return Err(status_error("Memory service", response).await);
+        }
+
+        let body = read_complete_body(response, "Memory service", self.max_response_bytes).await?;
+        memory_count_from_stats(&body)
+    }
+
+    /// Fetch one page of the user's raw memory records, optionally filtered
+    /// by tier.
+    pub async fn list_memories(
+        &self,
+        user_id: &str,
+        tier: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<Value>, McpError> {
+        self.circuit
+            .call(self.list_memories_inner(user_id, tier, limit, offset))
+            .await
+    }
+
+    async fn list_memories_inner(
+        &self,
+        user_id: &str,
+        tier: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<Value>, McpError> {
+        let mut url = format!(
+            "{}{}/memory/list?limit={}&offset={}",
+            self.base_url, self.path_prefix, limit, offset
+        );
+        if let Some(tier) = tier {
+            url.push_str(&format!("&tier={}", tier));
+        }
+
+        let response = downstream::send(
+            "memory_service",
+            &url,
+            self.client
+                .get(&url)
+                .timeout(self.default_timeout)
+                .header("X-User-Id", user_id),
+        )
+        .await?;
+
+        if response.status() != StatusCode::OK {
+            return Err(status_error("Memory service", response).await);
+        }
+
+        let body = read_complete_body(response, "Memory service", self.max_response_bytes).await?;
+        let mut result: Value = serde_json::from_slice(&body).map_err(|e| {
+            McpError::InternalError(format!("Failed to parse list response: {}", e))
+        })?;
+
+        let mut memories = match result["memories"].take() {
+            Value::Array(memories) => memories,
+            _ => return Err(McpError::InternalError("Invalid memory list format".to_string())),
+        };
+        for memory in &mut memories {
+            self.open_content(memory)?;
+        }
+        Ok(memories)
+    }
+
     pub async fn health_check(&self) -> bool {
-        let url = format!("{}/health", self.base_url);
-        self.client.get(&url).send().await.map(|r| r.status().is_success()).unwrap_or(false)
+        let url = format!("{}{}/health", self.base_url, self.path_prefix);
+        downstream::send(
+            "memory_service",
+            &url,
+            self.client.get(&url).timeout(self.health_timeout),
+        )
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
     }
 }
 
 pub struct IntelligenceServiceClient {
     base_url: String,
+    path_prefix: String,
     client: Client,
+    circuit: CircuitBreaker,
+    default_timeout: Duration,
+    health_timeout: Duration,
+    max_response_bytes: usize,
+    wire_format: WireFormat,
 }
 
 impl IntelligenceServiceClient {
     pub fn new(base_url: &str) -> Self {
         Self {
             base_url: base_url.to_string(),
+            path_prefix: String::new(),
             client: Client::new(),
+            circuit: CircuitBreaker::new(
+                "intelligence_service",
+                DEFAULT_CIRCUIT_FAILURE_THRESHOLD,
+                DEFAULT_CIRCUIT_RESET_TIMEOUT,
+            ),
+            default_timeout: DEFAULT_REQUEST_TIMEOUT,
+            health_timeout: DEFAULT_HEALTH_CHECK_TIMEOUT,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            wire_format: WireFormat::Json,
         }
     }
 
+    /// Timeout applied to calls that don't carry a per-request override.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// Timeout for `health_check`, kept short so probes fail fast.
+    pub fn with_health_timeout(mut self, timeout: Duration) -> Self {
+        self.health_timeout = timeout;
+        self
+    }
+
+    /// Largest response body accepted before the call fails.
+    pub fn with_max_response_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_response_bytes = max_bytes;
+        self
+    }
+
+    /// Encoding for the calls that support protobuf; JSON by default.
+    pub fn with_wire_format(mut self, format: WireFormat) -> Self {
+        self.wire_format = format;
+        self
+    }
+
+    /// Limit on establishing the TCP/TLS connection, separate from the
+    /// overall request timeout so slow connects fail fast while long reads
+    /// may continue.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.client = Client::builder()
+            .connect_timeout(timeout)
+            .build()
+            .expect("failed to build HTTP client");
+        self
+    }
+
+    /// Path prefix the intelligence service is mounted under (e.g. `/api/v1`).
+    /// Validated by `Config`.
+    pub fn with_path_prefix(mut self, path_prefix: &str) -> Self {
+        self.path_prefix = path_prefix.to_string();
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        self.circuit = CircuitBreaker::new("intelligence_service", failure_threshold, reset_timeout);
+        self
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        self.circuit.state()
+    }
+
     pub async fn send_message(
         &self,
         user_id: &str,
         message: &str,
         session_id: Option<uuid::Uuid>,
         use_memory: bool,
+        timeout: Option<Duration>,
+    ) -> Result<ChatMessageResponse, McpError> {
+        self.circuit
+            .call(self.send_message_inner(user_id, message, session_id, use_memory, timeout))
+            .await
+    }
+
+    async fn send_message_inner(
+        &self,
+        user_id: &str,
+        message: &str,
+        session_id: Option<uuid::Uuid>,
+        use_memory: bool,
+        timeout: Option<Duration>,
     ) -> Result<ChatMessageResponse, McpError> {
-        let url = format!("{}/chat/message", self.base_url);
+        let url = format!("{}{}/chat/message", self.base_url, self.path_prefix);
         
         let request_body = ChatMessageRequest {
             message: message.to_string(),
@@ -142,20 +767,31 @@ impl IntelligenceServiceClient {
             use_memory,
         };
 
-        let response = self.client
-            .post(&url)
-            .header("X-User-Id", user_id)
-            .json(&request_body)
-            .send()
-            .await?;
+        let response = downstream::send(
+            "intelligence_service",
+            &url,
+            wire::with_body::<_, wire::proto::ChatMessageRequest>(
+                self.client
+                    .post(&url)
+                    .timeout(timeout.unwrap_or(self.default_timeout))
+                    .header("X-User-Id", user_id),
+                self.wire_format,
+                &request_body,
+            ),
+        )
+        .await?;
 
         if response.status() != StatusCode::OK {
-            return Err(McpError::ServiceUnavailable(
-                format!("Intelligence service returned status: {}", response.status())
-            ));
+            return Err(status_error("Intelligence service", response).await);
         }
 
-        let result: ChatMessageResponse = response.json().await.map_err(|e| {
+        let protobuf = wire::is_protobuf(&response);
+        let body =
+            read_complete_body(response, "Intelligence service", self.max_response_bytes).await?;
+        if protobuf {
+            return wire::decode_chat_response(&body);
+        }
+        let result: ChatMessageResponse = serde_json::from_slice(&body).map_err(|e| {
             McpError::InternalError(format!("Failed to parse intelligence response: {}", e))
         })?;
 
@@ -163,7 +799,373 @@ impl IntelligenceServiceClient {
     }
 
     pub async fn health_check(&self) -> bool {
-        let url = format!("{}/health", self.base_url);
-        self.client.get(&url).send().await.map(|r| r.status().is_success()).unwrap_or(false)
+        let url = format!("{}{}/health", self.base_url, self.path_prefix);
+        downstream::send(
+            "intelligence_service",
+            &url,
+            self.client.get(&url).timeout(self.health_timeout),
+        )
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
+
+/// Build a `MemoryItem` from a memory service record, normalizing its
+/// confidence from a 0-`confidence_scale` range and, when asked, its
+/// timestamp to RFC 3339 UTC.
+fn memory_item_from_value(
+    m: &Value,
+    confidence_scale: f64,
+    normalize_timestamps: bool,
+) -> MemoryItem {
+    let raw_confidence = m["confidence_score"].as_f64();
+    let confidence_score = match raw_confidence {
+        Some(raw) if confidence_scale > 0.0 => (raw / confidence_scale).clamp(0.0, 1.0) as f32,
+        _ => 0.0,
+    };
+    MemoryItem {
+        id: m["id"].as_str().unwrap_or_default().to_string(),
+        user_id: m["user_id"].as_str().map(|s| s.to_string()),
+        content: format!(
+            "Input: {}\nOutput: {}",
+            m["input_context"].as_str().unwrap_or(""),
+            m["output_response"].as_str().unwrap_or("")
+        ),
+        tier: m["tier"].as_str().unwrap_or("ltm").to_string(),
+        confidence_score,
+        raw_confidence,
+        created_at: created_at_from_value(&m["created_at"], normalize_timestamps),
+        tags: m["tags"]
+            .as_array()
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(|tag| tag.as_str().map(|s| s.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        source: m["source"].as_str().map(|s| s.to_string()),
+        metadata: m.get("metadata").filter(|v| !v.is_null()).cloned(),
+        explanation: m
+            .get("explanation")
+            .filter(|v| !v.is_null())
+            .map(|factors| MemoryExplanation {
+                synthesized: false,
+                factors: factors.clone(),
+            }),
+    }
+}
+
+/// Fail closed unless `record` names `user_id` as its owner. A record
+/// without an owner is not treated as the caller's.
+fn ensure_owned_by(record: &Value, user_id: &str, memory_id: &str) -> Result<(), McpError> {
+    match record["user_id"].as_str() {
+        Some(owner) if owner == user_id => Ok(()),
+        owner => {
+            log::warn!(
+                "Memory service returned memory {} with owner {:?} to user {}",
+                memory_id,
+                owner,
+                user_id
+            );
+            Err(McpError::NotFound(format!("Memory {} not found", memory_id)))
+        }
+    }
+}
+
+/// `total_memories` from a `/memory/stats` response.
+fn memory_count_from_stats(body: &[u8]) -> Result<u64, McpError> {
+    let stats: Value = serde_json::from_slice(body).map_err(|e| {
+        McpError::InternalError(format!("Failed to parse stats response: {}", e))
+    })?;
+    stats["total_memories"]
+        .as_u64()
+        .ok_or_else(|| McpError::InternalError("Invalid memory stats format".to_string()))
+}
+
+/// Error for a downstream answer other than success. A 4xx is about this
+/// request (a user over quota, an unknown session, bad input), not the
+/// downstream's health, so it maps to the matching client error and carries
+/// the downstream's `detail`. Anything else is an outage.
+async fn status_error(service: &str, response: Response) -> McpError {
+    let status = response.status();
+    if !status.is_client_error() {
+        return McpError::ServiceUnavailable(format!("{} returned status: {}", service, status));
+    }
+
+    let retry_after_secs = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_DOWNSTREAM_RETRY_AFTER_SECS);
+    // FastAPI puts the reason in `detail`; validation errors carry a list
+    let detail = read_complete_body(response, service, MAX_ERROR_BODY_BYTES)
+        .await
+        .ok()
+        .and_then(|body| serde_json::from_slice::<Value>(&body).ok())
+        .and_then(|body| body["detail"].as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("{} rejected the request with status: {}", service, status));
+
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => McpError::TooManyRequests {
+            message: detail,
+            retry_after_secs,
+        },
+        StatusCode::NOT_FOUND => McpError::NotFound(detail),
+        _ => McpError::InvalidRequest(detail),
+    }
+}
+
+/// `created_at` as a string; epoch numbers are accepted too. Unparseable
+/// values are passed through unchanged.
+fn created_at_from_value(value: &Value, normalize: bool) -> String {
+    let raw = match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => return String::new(),
+    };
+    if !normalize {
+        return raw;
+    }
+    normalize_timestamp(&raw).unwrap_or_else(|| {
+        log::warn!("Passing through unparseable memory timestamp '{}'", raw);
+        raw
+    })
+}
+
+/// Read a downstream body in full, treating a dropped connection or a body
+/// shorter than the advertised Content-Length as an unavailable downstream
+/// rather than handing a truncated payload to the parser. Bodies larger than
+/// `max_bytes` are rejected as soon as the limit is crossed, so a misbehaving
+/// downstream can't make us buffer an unbounded payload.
+async fn read_complete_body(
+    mut response: Response,
+    service: &str,
+    max_bytes: usize,
+) -> Result<Vec<u8>, McpError> {
+    let expected_length = response.content_length();
+    if expected_length.is_some_and(|expected| expected > max_bytes as u64) {
+        log::warn!("{} advertised a response over {} bytes", service, max_bytes);
+        return Err(McpError::InternalError("downstream response too large".to_string()));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| {
+        McpError::ServiceUnavailable(format!("{} response was interrupted: {}", service, e))
+    })? {
+        if body.len() + chunk.len() > max_bytes {
+            log::warn!("{} response exceeded {} bytes", service, max_bytes);
+            return Err(McpError::InternalError("downstream response too large".to_string()));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    if let Some(expected) = expected_length {
+        if body.len() as u64 != expected {
+            return Err(McpError::ServiceUnavailable(format!(
+                "{} response incomplete: received {} of {} bytes",
+                service,
+                body.len(),
+                expected
+            )));
+        }
+    }
+
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owned_memory_is_accepted() {
+        let record = serde_json::json!({ "id": "m1", "user_id": "alice" });
+        assert!(ensure_owned_by(&record, "alice", "m1").is_ok());
+    }
+
+    #[test]
+    fn memory_of_another_user_or_without_owner_is_not_found() {
+        let other = serde_json::json!({ "id": "m1", "user_id": "bob" });
+        let unowned = serde_json::json!({ "id": "m1" });
+        let stats = serde_json::json!({ "total": 3, "memories": [] });
+        for record in [other, unowned, stats] {
+            assert!(matches!(
+                ensure_owned_by(&record, "alice", "m1"),
+                Err(McpError::NotFound(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn memory_count_reads_total_from_stats() {
+        let body = br#"{
+            "user_id": "user-1",
+            "stm_count": 2,
+            "itm_count": 3,
+            "ltm_count": 37,
+            "total_memories": 42,
+            "storage_used_mb": 1.5,
+            "storage_limit_mb": 10240.0,
+            "tier_breakdown": {"stm": 2, "itm": 3, "ltm": 37}
+        }"#;
+        assert_eq!(memory_count_from_stats(body).unwrap(), 42);
+    }
+
+    #[test]
+    fn memory_count_rejects_other_shapes() {
+        assert!(memory_count_from_stats(br#"{"count": 42}"#).is_err());
+        assert!(memory_count_from_stats(b"not json").is_err());
+    }
+
+    /// Serve one raw HTTP response on a local port and return its URL. The
+    /// connection is closed once `response` is written.
+    async fn serve_once(response: &'static [u8]) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            socket.write_all(response).await.unwrap();
+        });
+        format!("http://{}/", addr)
+    }
+
+    async fn read_from(response: &'static [u8], max_bytes: usize) -> Result<Vec<u8>, McpError> {
+        let url = serve_once(response).await;
+        let response = Client::new().get(&url).send().await.unwrap();
+        read_complete_body(response, "Test service", max_bytes).await
+    }
+
+    #[tokio::test]
+    async fn complete_body_is_returned() {
+        let body = read_from(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}", 1024).await;
+        assert_eq!(body.unwrap(), b"{}");
+    }
+
+    #[tokio::test]
+    async fn disconnect_mid_body_is_service_unavailable() {
+        let body = read_from(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n{\"partial\":",
+            1024,
+        )
+        .await;
+        assert!(matches!(body, Err(McpError::ServiceUnavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_rejected() {
+        let advertised =
+            read_from(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n", 10).await;
+        assert!(matches!(advertised, Err(McpError::InternalError(_))));
+
+        let chunked = read_from(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n14\r\n01234567890123456789\r\n0\r\n\r\n",
+            10,
+        )
+        .await;
+        assert!(matches!(chunked, Err(McpError::InternalError(_))));
+    }
+
+    async fn send_to(response: &'static [u8]) -> Result<ChatMessageResponse, McpError> {
+        let url = serve_once(response).await;
+        IntelligenceServiceClient::new(url.trim_end_matches('/'))
+            .send_message("alice", "hello", None, false, None)
+            .await
+    }
+
+    #[tokio::test]
+    async fn missing_session_id_follows_the_policy() {
+        use crate::config::MissingSessionPolicy;
+        use crate::routes::resolve_session_id;
+
+        let result = send_to(
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 16\r\n\r\n{\"response\":\"x\"}",
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.response, "x");
+        assert_eq!(result.session_id, "");
+        assert_eq!(result.tokens_used, None);
+
+        let requested = uuid::Uuid::new_v4();
+        let missing = result.session_id;
+        let reused = resolve_session_id(missing.clone(), Some(requested), MissingSessionPolicy::Reuse);
+        assert_eq!(reused.unwrap(), requested.to_string());
+        let failed = resolve_session_id(missing, None, MissingSessionPolicy::Fail);
+        assert!(matches!(failed, Err(McpError::InternalError(_))));
+    }
+
+    #[tokio::test]
+    async fn intelligence_client_errors_are_not_outages() {
+        let rejected =
+            send_to(b"HTTP/1.1 422 Unprocessable Entity\r\nContent-Length: 0\r\n\r\n").await;
+        assert!(matches!(rejected, Err(McpError::InvalidRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn intelligence_server_errors_are_outages() {
+        let failed =
+            send_to(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n").await;
+        assert!(matches!(failed, Err(McpError::ServiceUnavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn unreachable_intelligence_service_is_an_outage() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let result = IntelligenceServiceClient::new(&format!("http://{}", addr))
+            .send_message("alice", "hello", None, false, None)
+            .await;
+        assert!(matches!(result, Err(McpError::ServiceUnavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn intelligence_rate_limits_keep_their_detail_and_retry_after() {
+        let limited = send_to(
+            b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: 120\r\nContent-Type: application/json\r\nContent-Length: 39\r\n\r\n{\"detail\":\"Daily token limit exceeded\"}",
+        )
+        .await;
+        match limited {
+            Err(McpError::TooManyRequests { message, retry_after_secs }) => {
+                assert_eq!(message, "Daily token limit exceeded");
+                assert_eq!(retry_after_secs, 120);
+            }
+            other => panic!("expected a rate limit, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn intelligence_not_found_is_not_found() {
+        let missing = send_to(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await;
+        assert!(matches!(missing, Err(McpError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn memory_quota_rejections_leave_the_circuit_closed() {
+        let url = serve_once(
+            b"HTTP/1.1 429 Too Many Requests\r\nContent-Length: 35\r\n\r\n{\"detail\":\"Storage quota exceeded\"}",
+        )
+        .await;
+        let client = MemoryServiceClient::new(url.trim_end_matches('/'))
+            .with_circuit_breaker(1, Duration::from_secs(30));
+        let result = client.count("alice", None).await;
+        assert!(matches!(result, Err(McpError::TooManyRequests { .. })));
+        assert_eq!(client.circuit_state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn memory_server_errors_open_the_circuit() {
+        let url = serve_once(b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n").await;
+        let client = MemoryServiceClient::new(url.trim_end_matches('/'))
+            .with_circuit_breaker(1, Duration::from_secs(30));
+        let result = client.count("alice", None).await;
+        assert!(matches!(result, Err(McpError::ServiceUnavailable(_))));
+        assert_eq!(client.circuit_state(), CircuitState::Open);
     }
 }
@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arc_swap::ArcSwap;
+
+use crate::config::Config;
+use crate::errors::McpError;
+
+// Mutable runtime state shared across workers
+
+pub struct RuntimeState {
+    read_only: AtomicBool,
+    config: ArcSwap<Config>,
+    started_at: Instant,
+    requests_total: AtomicU64,
+    requests_in_flight: AtomicU64,
+}
+
+/// Counts a request as in flight until dropped.
+pub struct InFlightGuard<'a> {
+    state: &'a RuntimeState,
+}
+
+impl RuntimeState {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            read_only: AtomicBool::new(config.read_only),
+            config: ArcSwap::from_pointee(config.clone()),
+            started_at: Instant::now(),
+            requests_total: AtomicU64::new(0),
+            requests_in_flight: AtomicU64::new(0),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Record a request as served and in flight until the guard drops.
+    pub fn track_request(&self) -> InFlightGuard<'_> {
+        self.requests_total.fetch_add(1, Ordering::Relaxed);
+        self.requests_in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard { state: self }
+    }
+
+    pub fn requests_total(&self) -> u64 {
+        self.requests_total.load(Ordering::Relaxed)
+    }
+
+    pub fn requests_in_flight(&self) -> u64 {
+        self.requests_in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Current configuration, including any reloaded settings.
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    /// Apply the reloadable settings from `fresh` and return the names of
+    /// those that changed. Everything else keeps its startup value.
+    pub fn reload(&self, fresh: &Config) -> Vec<&'static str> {
+        let mut next = Config::clone(&self.config());
+        let mut changed = Vec::new();
+        macro_rules! reload {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if next.$field != fresh.$field {
+                        next.$field = fresh.$field.clone();
+                        changed.push(stringify!($field));
+                    }
+                )*
+            };
+        }
+        reload!(
+            max_request_timeout_ms,
+            jwt_clock_skew_secs,
+            rate_limits,
+            field_limits,
+            model_prices,
+            intelligence_model,
+            teams,
+            max_memory_tags,
+            max_tag_length,
+            store_sampling,
+            infer_memory_outcome,
+            known_languages,
+            infer_language_from_path,
+            tag_boost_factor,
+            stale_context_threshold_secs,
+            context_cache_max_age_secs,
+            min_context_confidence,
+            task_fallback_message,
+            missing_session_policy,
+            endpoints,
+        );
+        if !changed.is_empty() {
+            self.config.store(Arc::new(next));
+        }
+        changed
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    pub fn set_read_only(&self, enabled: bool) {
+        self.read_only.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Reject mutating calls while the server is in maintenance mode.
+    pub fn ensure_writable(&self) -> Result<(), McpError> {
+        if self.is_read_only() {
+            return Err(McpError::ServiceUnavailable(
+                "read-only maintenance mode, writes are temporarily disabled".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.state.requests_in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::OnceCell;
+
+use crate::models::ChatMessageResponse;
+
+// Single-flight deduplication of task submissions. An accidental double
+// submit of the same sessionless task shares one slot: the first caller
+// runs the intelligence call and later ones await and reuse its answer.
+// Slots are released once the call completes, so answers are never served
+// from a cache; the window only bounds how long a call stays joinable.
+// Failed calls leave the slot empty, so the next caller tries again.
+
+/// Upper bound on tasks in flight, in case the window is long.
+const MAX_ENTRIES: usize = 10_000;
+
+pub type TaskSlot = Arc<OnceCell<ChatMessageResponse>>;
+
+pub struct TaskDedup {
+    slots: Mutex<HashMap<u64, (Instant, TaskSlot)>>,
+    window: Duration,
+}
+
+impl TaskDedup {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            slots: Mutex::new(HashMap::new()),
+            window,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.window.is_zero()
+    }
+
+    /// The slot shared by submissions of `key` inside the window.
+    pub fn slot(&self, key: u64) -> TaskSlot {
+        let now = Instant::now();
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((created_at, slot)) = slots.get(&key) {
+            if now.duration_since(*created_at) < self.window {
+                return slot.clone();
+            }
+        }
+        if slots.len() >= MAX_ENTRIES {
+            slots.retain(|_, (created_at, _)| now.duration_since(*created_at) < self.window);
+        }
+        let slot = TaskSlot::default();
+        if slots.len() < MAX_ENTRIES {
+            slots.insert(key, (now, slot.clone()));
+        }
+        slot
+    }
+
+    /// Forget `slot` once its call has completed, so the next submission of
+    /// `key` makes a fresh call. A newer slot for the same key is kept.
+    pub fn release(&self, key: u64, slot: &TaskSlot) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        if slots.get(&key).is_some_and(|(_, current)| Arc::ptr_eq(current, slot)) {
+            slots.remove(&key);
+        }
+    }
+}
+
+/// Hash of what makes two submissions the same task.
+pub fn task_key(user_id: &str, message: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    user_id.hash(&mut hasher);
+    message.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: u64 = 42;
+
+    #[test]
+    fn duplicates_share_the_slot_while_in_flight() {
+        let dedup = TaskDedup::new(Duration::from_secs(10));
+        let first = dedup.slot(KEY);
+        let second = dedup.slot(KEY);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &dedup.slot(KEY + 1)));
+    }
+
+    #[test]
+    fn released_slots_are_not_reused() {
+        let dedup = TaskDedup::new(Duration::from_secs(10));
+        let first = dedup.slot(KEY);
+        dedup.release(KEY, &first);
+        assert!(!Arc::ptr_eq(&first, &dedup.slot(KEY)));
+    }
+
+    #[test]
+    fn releasing_an_old_slot_keeps_the_newer_one() {
+        let dedup = TaskDedup::new(Duration::from_secs(10));
+        let old = dedup.slot(KEY);
+        dedup.release(KEY, &old);
+        let newer = dedup.slot(KEY);
+        dedup.release(KEY, &old);
+        assert!(Arc::ptr_eq(&newer, &dedup.slot(KEY)));
+    }
+
+    #[test]
+    fn slots_expire_after_the_window() {
+        let dedup = TaskDedup::new(Duration::ZERO);
+        let first = dedup.slot(KEY);
+        assert!(!Arc::ptr_eq(&first, &dedup.slot(KEY)));
+    }
+}
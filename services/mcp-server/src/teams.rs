@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+
+use crate::errors::McpError;
+
+// Team membership for team-scoped context search. Teams and their members
+// come from config only; a client can name a team but never its members, so
+// a search can only widen to users the operator put in the same team.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TeamDirectory {
+    teams: HashMap<String, Vec<String>>,
+}
+
+impl TeamDirectory {
+    /// Parse `team=user|user` entries, e.g. `core=alice|bob,web=carol|dave`.
+    pub fn parse(spec: &[String]) -> Self {
+        let teams = spec
+            .iter()
+            .map(|entry| {
+                let (team, members) = entry
+                    .split_once('=')
+                    .unwrap_or_else(|| panic!("TEAM_MEMBERS entry '{}' must be team=user|user", entry));
+                let members: Vec<String> = members
+                    .split('|')
+                    .map(|member| member.trim().to_string())
+                    .filter(|member| !member.is_empty())
+                    .collect();
+                (team.trim().to_string(), members)
+            })
+            .collect();
+        Self { teams }
+    }
+
+    /// Members of `team`, provided `user_id` is one of them. Unknown teams
+    /// get the same answer as teams the user isn't in.
+    pub fn members_for(&self, team: &str, user_id: &str) -> Result<&[String], McpError> {
+        match self.teams.get(team) {
+            Some(members) if members.iter().any(|member| member == user_id) => Ok(members),
+            _ => Err(McpError::Forbidden(format!("Not a member of team '{}'", team))),
+        }
+    }
+}
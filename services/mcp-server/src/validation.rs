@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::errors::McpError;
+use crate::models::{ContextFetchRequest, MemoryImportRecord, MemoryLogRequest, TaskSubmitRequest};
+
+// Per-field size limits for request bodies, so a single oversized field is
+// rejected with a precise error instead of eating the whole body budget.
+// Limits are in characters and can be overridden per field name.
+
+const DEFAULT_FIELD_LIMITS: [(&str, usize); 8] = [
+    ("file_path", 4_096),
+    ("file_content", 1_000_000),
+    ("content", 1_000_000),
+    ("task_description", 50_000),
+    ("file_context", 1_000_000),
+    ("language", 64),
+    ("input_context", 1_000_000),
+    ("output_response", 1_000_000),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldLimits {
+    limits: HashMap<String, usize>,
+}
+
+impl FieldLimits {
+    /// Defaults overlaid with `field=max_chars` pairs, e.g.
+    /// `task_description=20000,content=50000`.
+    pub fn parse(spec: &[String]) -> Self {
+        let mut limits: HashMap<String, usize> = DEFAULT_FIELD_LIMITS
+            .iter()
+            .map(|(field, limit)| (field.to_string(), *limit))
+            .collect();
+        for entry in spec {
+            let (field, limit) = entry
+                .split_once('=')
+                .unwrap_or_else(|| panic!("FIELD_MAX_LENGTHS entry '{}' must be field=max", entry));
+            let limit: usize = limit
+                .trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("FIELD_MAX_LENGTHS limit '{}' is not a number", limit));
+            limits.insert(field.trim().to_string(), limit);
+        }
+        Self { limits }
+    }
+
+    pub fn check(&self, field: &str, value: &str) -> Result<(), McpError> {
+        match self.limits.get(field) {
+            Some(limit) if value.chars().count() > *limit => Err(McpError::InvalidRequest(
+                format!("Field '{}' exceeds maximum length of {} characters", field, limit),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_opt(&self, field: &str, value: Option<&str>) -> Result<(), McpError> {
+        value.map_or(Ok(()), |value| self.check(field, value))
+    }
+}
+
+/// Field-level validation for request bodies.
+pub trait Validate {
+    fn validate(&self, limits: &FieldLimits) -> Result<(), McpError>;
+}
+
+impl Validate for ContextFetchRequest {
+    fn validate(&self, limits: &FieldLimits) -> Result<(), McpError> {
+        limits.check("file_path", &self.file_path)?;
+        limits.check_opt("file_content", self.file_content.as_deref())?;
+        limits.check_opt("language", self.language.as_deref())?;
+        if let Some(min) = self.min_confidence {
+            if !(0.0..=1.0).contains(&min) {
+                return Err(McpError::InvalidRequest(
+                    "min_confidence must be between 0 and 1".to_string(),
+                ));
+            }
+        }
+        match (self.scope.as_deref(), &self.team_id) {
+            (None | Some("user"), None) | (Some("team"), Some(_)) => Ok(()),
+            (Some("team"), None) => Err(McpError::InvalidRequest(
+                "scope \"team\" requires team_id".to_string(),
+            )),
+            (None | Some("user"), Some(_)) => Err(McpError::InvalidRequest(
+                "team_id requires scope \"team\"".to_string(),
+            )),
+            (Some(scope), _) => Err(McpError::InvalidRequest(format!(
+                "scope must be \"user\" or \"team\", got '{}'",
+                scope
+            ))),
+        }
+    }
+}
+
+impl Validate for MemoryLogRequest {
+    fn validate(&self, limits: &FieldLimits) -> Result<(), McpError> {
+        limits.check("file_path", &self.file_path)?;
+        limits.check("action", &self.action)?;
+        limits.check_opt("content", self.content.as_deref())?;
+        limits.check_opt("outcome", self.outcome.as_deref())
+    }
+}
+
+impl Validate for TaskSubmitRequest {
+    fn validate(&self, limits: &FieldLimits) -> Result<(), McpError> {
+        limits.check("task_description", &self.task_description)?;
+        limits.check_opt("file_context", self.file_context.as_deref())?;
+        limits.check_opt("language", self.language.as_deref())
+    }
+}
+
+impl Validate for MemoryImportRecord {
+    fn validate(&self, limits: &FieldLimits) -> Result<(), McpError> {
+        limits.check("input_context", &self.input_context)?;
+        limits.check_opt("output_response", self.output_response.as_deref())?;
+        limits.check_opt("outcome", self.outcome.as_deref())
+    }
+}
+
+/// Memory ids end up in downstream URL paths, so only id characters are
+/// accepted.
+pub fn is_valid_memory_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
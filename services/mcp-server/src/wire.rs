@@ -0,0 +1,404 @@
+use prost::Message;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{RequestBuilder, Response};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::errors::McpError;
+use crate::models::{ChatMessageRequest, ChatMessageResponse, MemorySearchRequest, MemoryStoreRequest};
+
+// Downstream wire encoding. JSON is the default; a downstream configured for
+// protobuf is sent `application/x-protobuf` bodies and asked for protobuf
+// back. Responses are decoded by their Content-Type, so a downstream that
+// still answers in JSON keeps working. Schemas: proto/downstream.proto.
+
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+const PROTOBUF_ACCEPT: &str = "application/x-protobuf, application/json;q=0.5";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    Json,
+    Protobuf,
+}
+
+impl WireFormat {
+    pub fn parse(value: &str, key: &str) -> Self {
+        match value.trim() {
+            "json" => WireFormat::Json,
+            "protobuf" => WireFormat::Protobuf,
+            other => panic!("{} must be json or protobuf, got '{}'", key, other),
+        }
+    }
+}
+
+/// Messages from proto/downstream.proto.
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MemorySearchRequest {
+        #[prost(string, tag = "1")]
+        pub query: String,
+        #[prost(uint32, optional, tag = "2")]
+        pub limit: Option<u32>,
+        #[prost(string, optional, tag = "3")]
+        pub tier: Option<String>,
+        #[prost(string, repeated, tag = "4")]
+        pub boost_tags: Vec<String>,
+        #[prost(bool, optional, tag = "5")]
+        pub explain: Option<bool>,
+        #[prost(string, repeated, tag = "6")]
+        pub user_ids: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MemoryRecord {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, optional, tag = "2")]
+        pub user_id: Option<String>,
+        #[prost(string, tag = "3")]
+        pub input_context: String,
+        #[prost(string, optional, tag = "4")]
+        pub output_response: Option<String>,
+        #[prost(string, tag = "5")]
+        pub tier: String,
+        #[prost(double, optional, tag = "6")]
+        pub confidence_score: Option<f64>,
+        #[prost(string, tag = "7")]
+        pub created_at: String,
+        #[prost(string, repeated, tag = "8")]
+        pub tags: Vec<String>,
+        #[prost(string, optional, tag = "9")]
+        pub source: Option<String>,
+        #[prost(string, optional, tag = "10")]
+        pub metadata_json: Option<String>,
+        #[prost(string, optional, tag = "11")]
+        pub explanation_json: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MemorySearchResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub results: Vec<MemoryRecord>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MemoryStoreRequest {
+        #[prost(string, tag = "1")]
+        pub r#type: String,
+        #[prost(string, tag = "2")]
+        pub input_context: String,
+        #[prost(string, optional, tag = "3")]
+        pub output_response: Option<String>,
+        #[prost(string, optional, tag = "4")]
+        pub outcome: Option<String>,
+        #[prost(string, tag = "5")]
+        pub tier: String,
+        #[prost(string, repeated, tag = "6")]
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MemoryStoreResponse {
+        #[prost(string, tag = "1")]
+        pub id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ChatMessageRequest {
+        #[prost(string, tag = "1")]
+        pub message: String,
+        #[prost(string, optional, tag = "2")]
+        pub session_id: Option<String>,
+        #[prost(bool, tag = "3")]
+        pub use_memory: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ChatMessageResponse {
+        #[prost(string, tag = "1")]
+        pub session_id: String,
+        #[prost(string, tag = "2")]
+        pub response: String,
+        #[prost(int32, optional, tag = "3")]
+        pub tokens_used: Option<i32>,
+    }
+}
+
+impl From<&MemorySearchRequest> for proto::MemorySearchRequest {
+    fn from(request: &MemorySearchRequest) -> Self {
+        Self {
+            query: request.query.clone(),
+            limit: request.limit.map(|limit| limit as u32),
+            tier: request.tier.clone(),
+            boost_tags: request.boost_tags.clone().unwrap_or_default(),
+            explain: request.explain,
+            user_ids: request.user_ids.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&MemoryStoreRequest> for proto::MemoryStoreRequest {
+    fn from(request: &MemoryStoreRequest) -> Self {
+        Self {
+            r#type: request.memory_type.clone(),
+            input_context: request.input_context.clone(),
+            output_response: request.output_response.clone(),
+            outcome: request.outcome.clone(),
+            tier: request.tier.clone(),
+            tags: request.tags.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&ChatMessageRequest> for proto::ChatMessageRequest {
+    fn from(request: &ChatMessageRequest) -> Self {
+        Self {
+            message: request.message.clone(),
+            session_id: request.session_id.map(|id| id.to_string()),
+            use_memory: request.use_memory,
+        }
+    }
+}
+
+impl From<proto::ChatMessageResponse> for ChatMessageResponse {
+    fn from(response: proto::ChatMessageResponse) -> Self {
+        Self {
+            session_id: response.session_id,
+            response: response.response,
+            tokens_used: response.tokens_used,
+        }
+    }
+}
+
+/// Attach `body` in `format`; `P` is its protobuf counterpart.
+pub fn with_body<'a, T, P>(builder: RequestBuilder, format: WireFormat, body: &'a T) -> RequestBuilder
+where
+    T: Serialize,
+    P: Message + From<&'a T>,
+{
+    match format {
+        WireFormat::Json => builder.json(body),
+        WireFormat::Protobuf => builder
+            .header(CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
+            .header(ACCEPT, PROTOBUF_ACCEPT)
+            .body(P::from(body).encode_to_vec()),
+    }
+}
+
+/// Whether `response` carries a protobuf body.
+pub fn is_protobuf(response: &Response) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with(PROTOBUF_CONTENT_TYPE))
+        .unwrap_or(false)
+}
+
+fn decode<M: Message + Default>(body: &[u8], what: &str) -> Result<M, McpError> {
+    M::decode(body)
+        .map_err(|e| McpError::InternalError(format!("Failed to decode {}: {}", what, e)))
+}
+
+/// A protobuf search response in the JSON shape of `/memory/search`, so
+/// both encodings share one record parser.
+pub fn decode_search_response(body: &[u8]) -> Result<Value, McpError> {
+    let response: proto::MemorySearchResponse = decode(body, "memory search response")?;
+    let results: Vec<Value> = response
+        .results
+        .into_iter()
+        .map(|record| {
+            let embedded_json = |text: Option<String>| {
+                text.and_then(|text| serde_json::from_str::<Value>(&text).ok())
+                    .unwrap_or(Value::Null)
+            };
+            serde_json::json!({
+                "id": record.id,
+                "user_id": record.user_id,
+                "input_context": record.input_context,
+                "output_response": record.output_response,
+                "tier": record.tier,
+                "confidence_score": record.confidence_score,
+                "created_at": record.created_at,
+                "tags": record.tags,
+                "source": record.source,
+                "metadata": embedded_json(record.metadata_json),
+                "explanation": embedded_json(record.explanation_json),
+            })
+        })
+        .collect();
+    Ok(serde_json::json!({ "results": results }))
+}
+
+pub fn decode_store_response(body: &[u8]) -> Result<String, McpError> {
+    let response: proto::MemoryStoreResponse = decode(body, "memory store response")?;
+    Ok(response.id)
+}
+
+pub fn decode_chat_response(body: &[u8]) -> Result<ChatMessageResponse, McpError> {
+    let response: proto::ChatMessageResponse = decode(body, "intelligence response")?;
+    Ok(response.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::encoding::{decode_key, skip_field, DecodeContext, WireType};
+    use std::collections::BTreeMap;
+    use std::fmt::Debug;
+
+    const SCHEMA: &str = include_str!("../proto/downstream.proto");
+
+    fn wire_type(proto_type: &str) -> WireType {
+        match proto_type {
+            "string" => WireType::LengthDelimited,
+            "bool" | "int32" | "uint32" => WireType::Varint,
+            "double" => WireType::SixtyFourBit,
+            "float" => WireType::ThirtyTwoBit,
+            message if message.starts_with(char::is_uppercase) => WireType::LengthDelimited,
+            other => panic!("unhandled proto type {}", other),
+        }
+    }
+
+    /// Tag and wire type of every field, per message in downstream.proto.
+    fn schema() -> BTreeMap<String, Vec<(u32, WireType)>> {
+        let mut messages = BTreeMap::new();
+        let mut current: Option<(String, Vec<(u32, WireType)>)> = None;
+        for line in SCHEMA.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix("message ") {
+                current = Some((name.trim_end_matches('{').trim().to_string(), Vec::new()));
+            } else if line == "}" {
+                if let Some((name, fields)) = current.take() {
+                    messages.insert(name, fields);
+                }
+            } else if let Some((_, fields)) = current.as_mut() {
+                let Some(field) = line.strip_suffix(';') else {
+                    continue;
+                };
+                let words: Vec<&str> = field
+                    .split_whitespace()
+                    .filter(|word| *word != "optional" && *word != "repeated")
+                    .collect();
+                if let [proto_type, _, "=", tag] = words[..] {
+                    fields.push((tag.parse().unwrap(), wire_type(proto_type)));
+                }
+            }
+        }
+        messages
+    }
+
+    /// Tag and wire type of every field present in the encoding of `message`.
+    fn encoded_fields<M: Message>(message: &M) -> Vec<(u32, WireType)> {
+        let bytes = message.encode_to_vec();
+        let mut buf = bytes.as_slice();
+        let mut fields = Vec::new();
+        while !buf.is_empty() {
+            let (tag, wire_type) = decode_key(&mut buf).unwrap();
+            skip_field(wire_type, tag, &mut buf, DecodeContext::default()).unwrap();
+            fields.push((tag, wire_type));
+        }
+        fields
+    }
+
+    /// `message` must set every field to a non-default value, so each one
+    /// shows up in its encoding.
+    fn assert_matches_schema<M>(
+        schema: &mut BTreeMap<String, Vec<(u32, WireType)>>,
+        name: &str,
+        message: M,
+    ) where
+        M: Message + Default + PartialEq + Debug,
+    {
+        let expected = schema
+            .remove(name)
+            .unwrap_or_else(|| panic!("{} is not in downstream.proto", name));
+        assert_eq!(encoded_fields(&message), expected, "{} differs from downstream.proto", name);
+        assert_eq!(M::decode(message.encode_to_vec().as_slice()).unwrap(), message);
+    }
+
+    fn record() -> proto::MemoryRecord {
+        proto::MemoryRecord {
+            id: "m1".to_string(),
+            user_id: Some("alice".to_string()),
+            input_context: "fn main() {}".to_string(),
+            output_response: Some("ok".to_string()),
+            tier: "ltm".to_string(),
+            confidence_score: Some(0.75),
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            tags: vec!["rust".to_string()],
+            source: Some("editor".to_string()),
+            metadata_json: Some(r#"{"line":1}"#.to_string()),
+            explanation_json: Some(r#"{"recency":0.5}"#.to_string()),
+        }
+    }
+
+    #[test]
+    fn messages_round_trip_with_the_tags_of_downstream_proto() {
+        let mut schema = schema();
+        assert_matches_schema(
+            &mut schema,
+            "MemorySearchRequest",
+            proto::MemorySearchRequest {
+                query: "query".to_string(),
+                limit: Some(5),
+                tier: Some("ltm".to_string()),
+                boost_tags: vec!["rust".to_string()],
+                explain: Some(true),
+                user_ids: vec!["alice".to_string()],
+            },
+        );
+        assert_matches_schema(&mut schema, "MemoryRecord", record());
+        assert_matches_schema(
+            &mut schema,
+            "MemorySearchResponse",
+            proto::MemorySearchResponse { results: vec![record()] },
+        );
+        assert_matches_schema(
+            &mut schema,
+            "MemoryStoreRequest",
+            proto::MemoryStoreRequest {
+                r#type: "code_interaction".to_string(),
+                input_context: "fn main() {}".to_string(),
+                output_response: Some("ok".to_string()),
+                outcome: Some("success".to_string()),
+                tier: "stm".to_string(),
+                tags: vec!["rust".to_string()],
+            },
+        );
+        assert_matches_schema(
+            &mut schema,
+            "MemoryStoreResponse",
+            proto::MemoryStoreResponse { id: "m1".to_string() },
+        );
+        assert_matches_schema(
+            &mut schema,
+            "ChatMessageRequest",
+            proto::ChatMessageRequest {
+                message: "hello".to_string(),
+                session_id: Some("8d3f6c1e-0000-4000-8000-000000000000".to_string()),
+                use_memory: true,
+            },
+        );
+        assert_matches_schema(
+            &mut schema,
+            "ChatMessageResponse",
+            proto::ChatMessageResponse {
+                session_id: "8d3f6c1e-0000-4000-8000-000000000000".to_string(),
+                response: "hi".to_string(),
+                tokens_used: Some(12),
+            },
+        );
+        assert!(schema.is_empty(), "messages without a Rust counterpart: {:?}", schema.keys());
+    }
+
+    #[test]
+    fn protobuf_search_response_decodes_to_the_json_shape() {
+        let body = proto::MemorySearchResponse { results: vec![record()] }.encode_to_vec();
+        let decoded = decode_search_response(&body).unwrap();
+        let result = &decoded["results"][0];
+        assert_eq!(result["id"], "m1");
+        assert_eq!(result["confidence_score"], 0.75);
+        assert_eq!(result["metadata"]["line"], 1);
+        assert_eq!(result["explanation"]["recency"], 0.5);
+    }
+}
@@ -20,6 +20,26 @@ export interface TaskResponse {
     tokens_used?: number;
 }
 
+export type CircuitState = 'closed' | 'open' | 'half_open';
+
+export type DependencyStatus = 'up' | 'down_optional' | 'down_required';
+
+export interface HealthResponse {
+    status: 'healthy' | 'degraded';
+    version: string;
+    memory_service: boolean;
+    intelligence_service: boolean;
+    read_only: boolean;
+    circuits: {
+        memory_service: CircuitState;
+        intelligence_service: CircuitState;
+    };
+    dependencies: {
+        memory_service: DependencyStatus;
+        intelligence_service: DependencyStatus;
+    };
+}
+
 export class McpClient {
     private authManager: AuthManager;
     private client: AxiosInstance;
@@ -86,10 +106,19 @@ export class McpClient {
         return response.data;
     }
 
+    /**
+     * The server's self-reported health, including circuit breaker and
+     * dependency states, for callers that route around a degraded server.
+     */
+    async health(): Promise<HealthResponse> {
+        const response = await this.client.get<HealthResponse>('/api/mcp/health');
+        return response.data;
+    }
+
     async healthCheck(): Promise<boolean> {
         try {
-            const response = await this.client.get('/api/mcp/health');
-            return response.data.status === 'healthy';
+            const health = await this.health();
+            return health.status === 'healthy';
         } catch (error) {
             return false;
         }